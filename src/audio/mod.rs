@@ -1,39 +1,68 @@
 /// Audio output using cpal
+use crate::output::EventSink;
+use crate::sequencer::playback::PlaybackEvent;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use std::sync::{Arc, Mutex};
 
+/// Time constant of the pitch glide used for slides
+const GLIDE_SECONDS: f32 = 0.06;
+
+/// Decay time constant of the metronome click
+const CLICK_SECONDS: f32 = 0.015;
+
 pub struct AudioOutput {
     _stream: Option<cpal::Stream>,
-    phase: Arc<Mutex<f32>>,
+    _phase: Arc<Mutex<f32>>,
     trigger: Arc<Mutex<Option<f32>>>,
+    glide: Arc<Mutex<bool>>,
+    click: Arc<Mutex<Option<f32>>>,
+    click_enabled: bool,
+    sounding: Option<u8>,
 }
 
 impl AudioOutput {
     pub fn new() -> Option<Self> {
         let phase = Arc::new(Mutex::new(0.0));
         let trigger = Arc::new(Mutex::new(None));
+        let glide = Arc::new(Mutex::new(false));
+        let click = Arc::new(Mutex::new(None));
         
         let phase_clone = Arc::clone(&phase);
         let trigger_clone = Arc::clone(&trigger);
+        let glide_clone = Arc::clone(&glide);
+        let click_clone = Arc::clone(&click);
         
-        let stream = Self::setup_audio_stream(phase_clone, trigger_clone)?;
+        let stream =
+            Self::setup_audio_stream(phase_clone, trigger_clone, glide_clone, click_clone)?;
         
         Some(Self {
             _stream: Some(stream),
-            phase,
+            _phase: phase,
             trigger,
+            glide,
+            click,
+            click_enabled: true,
+            sounding: None,
         })
     }
 
     fn setup_audio_stream(
         phase: Arc<Mutex<f32>>,
         trigger: Arc<Mutex<Option<f32>>>,
+        glide: Arc<Mutex<bool>>,
+        click: Arc<Mutex<Option<f32>>>,
     ) -> Option<cpal::Stream> {
         let host = cpal::default_host();
         let device = host.default_output_device()?;
         let config = device.default_output_config().ok()?;
         
         let sample_rate = config.sample_rate().0 as f32;
+        let glide_coefficient = 1.0 - (-1.0 / (GLIDE_SECONDS * sample_rate)).exp();
+        let click_decay = (-1.0 / (CLICK_SECONDS * sample_rate)).exp();
+        let mut frequency = 0.0;
+        let mut click_frequency = 0.0;
+        let mut click_phase = 0.0_f32;
+        let mut click_level = 0.0_f32;
         
         let stream = match config.sample_format() {
             cpal::SampleFormat::F32 => {
@@ -41,10 +70,22 @@ impl AudioOutput {
                     &config.into(),
                     move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
                         let mut phase_lock = phase.lock().unwrap();
-                        let mut trigger_lock = trigger.lock().unwrap();
+                        let trigger_lock = trigger.lock().unwrap();
+                        let gliding = *glide.lock().unwrap();
+                        if let Some(pitch) = click.lock().unwrap().take() {
+                            click_frequency = pitch;
+                            click_phase = 0.0;
+                            click_level = 1.0;
+                        }
                         
                         for sample in data.iter_mut() {
-                            if let Some(frequency) = *trigger_lock {
+                            if let Some(target) = *trigger_lock {
+                                // Slides glide towards the new pitch, other notes jump to it
+                                if gliding && frequency > 0.0 {
+                                    frequency += (target - frequency) * glide_coefficient;
+                                } else {
+                                    frequency = target;
+                                }
                                 let phase_increment = frequency / sample_rate;
                                 *sample = (*phase_lock * 2.0 * std::f32::consts::PI).sin() * 0.2;
                                 *phase_lock += phase_increment;
@@ -54,6 +95,16 @@ impl AudioOutput {
                             } else {
                                 *sample = 0.0;
                                 *phase_lock = 0.0;
+                                frequency = 0.0;
+                            }
+
+                            // The click is a short decaying sine mixed over the synth
+                            if click_level > 0.001 {
+                                *sample += (click_phase * 2.0 * std::f32::consts::PI).sin()
+                                    * click_level
+                                    * 0.3;
+                                click_phase = (click_phase + click_frequency / sample_rate) % 1.0;
+                                click_level *= click_decay;
                             }
                         }
                     },
@@ -74,11 +125,34 @@ impl AudioOutput {
 
     pub fn trigger_note(&mut self, note: u8) {
         let frequency = midi_note_to_frequency(note);
+        *self.glide.lock().unwrap() = false;
+        *self.trigger.lock().unwrap() = Some(frequency);
+        self.sounding = Some(note);
+    }
+
+    /// Move to a new note with a pitch glide instead of jumping to it
+    pub fn glide_to(&mut self, note: u8) {
+        let frequency = midi_note_to_frequency(note);
+        *self.glide.lock().unwrap() = true;
         *self.trigger.lock().unwrap() = Some(frequency);
+        self.sounding = Some(note);
+    }
+
+    /// Play a metronome click, higher pitched on the downbeat
+    pub fn click(&mut self, accent: bool) {
+        if self.click_enabled {
+            *self.click.lock().unwrap() = Some(if accent { 1500.0 } else { 1000.0 });
+        }
+    }
+
+    /// Turn the audio click off, e.g. when it is sent to a MIDI note instead
+    pub fn set_click_enabled(&mut self, enabled: bool) {
+        self.click_enabled = enabled;
     }
 
     pub fn stop_note(&mut self) {
         *self.trigger.lock().unwrap() = None;
+        self.sounding = None;
     }
 }
 
@@ -86,12 +160,30 @@ impl Default for AudioOutput {
     fn default() -> Self {
         Self::new().unwrap_or_else(|| Self {
             _stream: None,
-            phase: Arc::new(Mutex::new(0.0)),
+            _phase: Arc::new(Mutex::new(0.0)),
             trigger: Arc::new(Mutex::new(None)),
+            glide: Arc::new(Mutex::new(false)),
+            click: Arc::new(Mutex::new(None)),
+            click_enabled: true,
+            sounding: None,
         })
     }
 }
 
+impl EventSink for AudioOutput {
+    fn handle_event(&mut self, event: &PlaybackEvent) -> Result<(), String> {
+        match *event {
+            PlaybackEvent::NoteOn(note, _) => self.trigger_note(note),
+            PlaybackEvent::SlideTo(note, _) => self.glide_to(note),
+            PlaybackEvent::Click(accent) => self.click(accent),
+            // The synth is monophonic: ignore releases of notes already replaced
+            PlaybackEvent::NoteOff(note) if self.sounding == Some(note) => self.stop_note(),
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
 fn midi_note_to_frequency(note: u8) -> f32 {
     440.0 * 2.0_f32.powf((note as f32 - 69.0) / 12.0)
 }
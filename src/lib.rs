@@ -1,18 +1,27 @@
-/// SQNC - A modular step sequencer library
-/// 
-/// This library provides the core components for building step sequencers:
-/// - Grid-based sequencing with flexible grid sizes
-/// - Audio output for testing
-/// - MIDI output for production use
-/// - Playback engine for timing and coordination
+//! SQNC - A modular step sequencer library
+//!
+//! This library provides the core components for building step sequencers:
+//! - Grid-based sequencing with flexible grid sizes
+//! - Audio output for testing
+//! - MIDI output for production use
+//! - Playback engine for timing and coordination
+//! - Pluggable output sinks for playback events
 
-pub mod sequencer;
 pub mod audio;
 pub mod midi;
+pub mod output;
+pub mod sequencer;
 
 // Re-export commonly used types
-pub use sequencer::{Grid, Sequencer};
-pub use sequencer::playback::{PlaybackEngine, PlaybackEvent};
 pub use audio::AudioOutput;
-pub use midi::{MidiOutputDevice, midi_note_name};
-
+pub use midi::scale::{Key, Scale};
+pub use midi::{midi_note_name, parse_note_name, parse_note_name_with_octave, MidiOutputDevice};
+pub use output::{EventDispatcher, EventSink};
+pub use sequencer::euclid::euclidean;
+pub use sequencer::playback::{PlaybackEngine, PlaybackEvent};
+pub use sequencer::{
+    Arrangement, CellChange, Clip, Condition, Direction, EditCommand, EditTarget, Grid, Groove,
+    History, Metronome, MusicalPosition, PasteMode, PatternBank, Rate, Region, ResizeMode,
+    Resolution, Sequencer, Song, SongEntry, SongPosition, Step, TapTempo, TempoChange, TempoCurve,
+    TempoRamp, TimeSignature, Track, Traversal, TriggerContext, VelocityRamp,
+};
@@ -1,11 +1,43 @@
 #[cfg(feature = "gui")]
 use eframe::egui;
 
+#[cfg(feature = "gui")]
+use std::cell::RefCell;
+#[cfg(feature = "gui")]
+use std::rc::Rc;
+#[cfg(feature = "gui")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "gui")]
+use std::time::{Duration, Instant};
+
 #[cfg(feature = "gui")]
 use sqnc::{
-    midi_note_name, AudioOutput, MidiOutputDevice, PlaybackEngine, PlaybackEvent, Sequencer,
+    midi_note_name, AudioOutput, Clip, Condition, Direction, EventDispatcher, Grid, Groove,
+    MidiOutputDevice, MusicalPosition, PasteMode, PlaybackEngine, PlaybackEvent, Rate, Region,
+    ResizeMode, Resolution, Scale, Sequencer, SongEntry, Step, TempoChange, TempoCurve,
+    TimeSignature, Traversal, VelocityRamp,
 };
 
+/// General MIDI metronome click, used when the click goes to a MIDI note
+#[cfg(feature = "gui")]
+const DEFAULT_CLICK_NOTE: u8 = 33;
+
+/// How far the tempo nudge buttons and keys push the tempo while held
+#[cfg(feature = "gui")]
+const TEMPO_NUDGE_AMOUNT: f32 = 0.04;
+
+/// Step buttons shrink to fit wide grids down to this width, then scroll
+#[cfg(feature = "gui")]
+const MIN_CELL_WIDTH: f32 = 40.0;
+
+/// Tallest the step grid grows before it scrolls
+#[cfg(feature = "gui")]
+const GRID_MAX_HEIGHT: f32 = 480.0;
+
+/// How often the MIDI port list is rescanned for hot-plugged devices
+#[cfg(feature = "gui")]
+const MIDI_RESCAN_INTERVAL: Duration = Duration::from_secs(2);
+
 #[cfg(feature = "gui")]
 fn main() -> Result<(), eframe::Error> {
     let options = eframe::NativeOptions {
@@ -28,32 +60,213 @@ fn main() {
     std::process::exit(1);
 }
 
+/// Per-step editor shown in a step's context menu; returns true if anything changed
+#[cfg(feature = "gui")]
+fn step_menu(ui: &mut egui::Ui, grid: &mut Grid, x: usize, y: usize) -> bool {
+    let Some(step) = grid.step_mut(x, y) else {
+        return false;
+    };
+    let mut changed = false;
+
+    ui.label("Scale degree:");
+    changed |= ui
+        .add(egui::DragValue::new(&mut step.degree).range(-24..=24))
+        .changed();
+
+    ui.label("Probability:");
+    changed |= ui
+        .add(egui::Slider::new(&mut step.probability, 0..=100).suffix("%"))
+        .changed();
+
+    ui.horizontal(|ui| {
+        ui.label("Ratchet:");
+        changed |= ui
+            .add(
+                egui::DragValue::new(&mut step.ratchet)
+                    .range(1..=8)
+                    .prefix("×"),
+            )
+            .changed();
+        egui::ComboBox::from_id_source("velocity_ramp")
+            .selected_text(step.velocity_ramp.name())
+            .show_ui(ui, |ui| {
+                for ramp in VelocityRamp::ALL {
+                    changed |= ui
+                        .selectable_value(&mut step.velocity_ramp, ramp, ramp.name())
+                        .changed();
+                }
+            });
+    });
+
+    ui.label("Gate (steps):");
+    changed |= ui
+        .add(
+            egui::Slider::new(&mut step.gate, Step::MIN_GATE..=Step::MAX_GATE)
+                .logarithmic(true)
+                .max_decimals(2),
+        )
+        .changed();
+    ui.label("Nudge:");
+    changed |= ui
+        .add(
+            egui::Slider::new(&mut step.nudge, -Step::MAX_NUDGE..=Step::MAX_NUDGE)
+                .custom_formatter(|ticks, _| {
+                    format!("{:+.0}%", ticks * 100.0 / Step::NUDGE_TICKS_PER_STEP as f64)
+                }),
+        )
+        .changed();
+    ui.horizontal(|ui| {
+        changed |= ui.checkbox(&mut step.tie, "Tie").changed();
+        changed |= ui.checkbox(&mut step.slide, "Slide").changed();
+    });
+
+    ui.label("Condition:");
+    egui::ComboBox::from_id_source("condition")
+        .selected_text(step.condition.name())
+        .show_ui(ui, |ui| {
+            for condition in Condition::ALL {
+                let is_selected =
+                    std::mem::discriminant(&step.condition) == std::mem::discriminant(&condition);
+                if ui.selectable_label(is_selected, condition.name()).clicked() && !is_selected {
+                    step.condition = condition;
+                    changed = true;
+                }
+            }
+        });
+    if let Condition::Ratio(a, b) = &mut step.condition {
+        ui.horizontal(|ui| {
+            ui.label("Loop");
+            changed |= ui.add(egui::DragValue::new(a).range(1..=8)).changed();
+            ui.label("of every");
+            changed |= ui.add(egui::DragValue::new(b).range(2..=8)).changed();
+            *a = (*a).min(*b);
+        });
+    }
+
+    changed
+}
+
+/// Inline editor for a tempo change; returns true if anything changed
+#[cfg(feature = "gui")]
+fn tempo_change_editor(ui: &mut egui::Ui, change: &mut TempoChange) -> bool {
+    let mut changed = ui
+        .add(
+            egui::DragValue::new(&mut change.bpm)
+                .range(Sequencer::MIN_BPM..=Sequencer::MAX_BPM)
+                .speed(0.1)
+                .suffix(" BPM"),
+        )
+        .changed();
+    ui.label("over");
+    changed |= ui
+        .add(
+            egui::DragValue::new(&mut change.bars)
+                .range(0..=64)
+                .suffix(" bars"),
+        )
+        .changed();
+    if change.bars > 0 && ui.small_button(change.curve.name()).clicked() {
+        let index = TempoCurve::ALL
+            .iter()
+            .position(|c| *c == change.curve)
+            .unwrap_or(0);
+        change.curve = TempoCurve::ALL[(index + 1) % TempoCurve::ALL.len()];
+        changed = true;
+    }
+    changed
+}
+
+#[cfg(feature = "gui")]
+enum PatternAction {
+    Switch(usize),
+    CopyTo(usize),
+    Clear(usize),
+}
+
+/// Per-row (track) transform buttons
+#[cfg(feature = "gui")]
+#[derive(Clone, Copy)]
+enum RowTransform {
+    Shift(i32),
+    Reverse,
+    Invert,
+}
+
 #[cfg(feature = "gui")]
 struct SequencerApp {
     sequencer: Sequencer,
-    audio_output: AudioOutput,
-    midi_output: MidiOutputDevice,
+    midi_output: Arc<Mutex<MidiOutputDevice>>,
+    audio_output: Rc<RefCell<AudioOutput>>,
+    dispatcher: EventDispatcher,
     playback_engine: PlaybackEngine,
 
     // UI state
     available_midi_ports: Vec<String>,
-    selected_port: Option<usize>,
+    last_midi_rescan: Instant,
     current_visual_step: usize,
+    track_positions: Vec<usize>, // local playhead per track in track mode
+    musical_position: MusicalPosition,
+    tempo_draft: (usize, TempoChange), // tempo change being set up before adding it
+    euclid_settings: Vec<(usize, usize)>, // (hits, rotation) per row
+    note_input: String,
+    note_input_error: bool,
+    drawn_path: Option<Vec<(usize, usize)>>, // custom traversal being drawn
+    groove_path: String,
+    groove_error: Option<String>,
+    export_path: String,
+    export_status: Option<Result<(), String>>,
+    selection: Option<((usize, usize), (usize, usize))>, // shift-click (anchor, corner)
+    clipboard: Option<Clip>,
+    paste_mode: PasteMode,
+    clipboard_error: Option<String>,
+    resize_draft: (usize, usize, ResizeMode), // size being set up before applying it
 }
 
 #[cfg(feature = "gui")]
 impl SequencerApp {
     fn new() -> Self {
         let available_midi_ports = MidiOutputDevice::available_ports();
+        let midi_output = Arc::new(Mutex::new(MidiOutputDevice::new()));
+
+        let audio_output = Rc::new(RefCell::new(AudioOutput::default()));
+
+        let mut dispatcher = EventDispatcher::new();
+        dispatcher.add_sink(Rc::clone(&audio_output));
+        dispatcher.add_sink(Arc::clone(&midi_output));
+
+        let sequencer = Sequencer::new(8, 8);
+        let euclid_settings = vec![(0, 0); sequencer.grid().height()];
+        let resize_draft = (
+            sequencer.grid().width(),
+            sequencer.grid().height(),
+            ResizeMode::default(),
+        );
 
         Self {
-            sequencer: Sequencer::new(8, 8), // Start with 16x1 for compatibility
-            audio_output: AudioOutput::default(),
-            midi_output: MidiOutputDevice::new(),
+            sequencer,
+            midi_output,
+            audio_output,
+            dispatcher,
             playback_engine: PlaybackEngine::new(),
             available_midi_ports,
-            selected_port: None,
+            last_midi_rescan: Instant::now(),
             current_visual_step: 0,
+            track_positions: Vec::new(),
+            musical_position: MusicalPosition::default(),
+            tempo_draft: (0, TempoChange::ramp(140.0, 4, TempoCurve::Linear)),
+            euclid_settings,
+            note_input: midi_note_name(60),
+            note_input_error: false,
+            drawn_path: None,
+            groove_path: String::new(),
+            groove_error: None,
+            export_path: "song.mid".to_string(),
+            export_status: None,
+            selection: None,
+            clipboard: None,
+            paste_mode: PasteMode::default(),
+            clipboard_error: None,
+            resize_draft,
         }
     }
 
@@ -66,33 +279,526 @@ impl SequencerApp {
                     self.current_visual_step = step;
                     self.sequencer.set_current_position(step);
                 }
-                PlaybackEvent::NoteOn(note, velocity) => {
-                    self.audio_output.trigger_note(note);
-                    let _ = self.midi_output.send_note_on(note, velocity);
+                PlaybackEvent::TempoChanged(bpm) => {
+                    self.sequencer.set_current_bpm(bpm);
+                }
+                PlaybackEvent::Position(position) => {
+                    self.musical_position = position;
+                }
+                PlaybackEvent::TrackStepAdvanced(track, step) => {
+                    if self.track_positions.len() <= track {
+                        self.track_positions.resize(track + 1, 0);
+                    }
+                    self.track_positions[track] = step;
                 }
-                PlaybackEvent::NoteOff(note) => {
-                    self.audio_output.stop_note();
-                    let _ = self.midi_output.send_note_off(note);
+                PlaybackEvent::PatternChanged(index) => {
+                    self.sequencer.set_current_pattern(index);
                 }
+                PlaybackEvent::SongEnded => {
+                    self.stop_playback();
+                }
+                _ => {}
+            }
+            let _ = self.dispatcher.dispatch(&event);
+        }
+    }
+
+    fn rescan_midi_ports(&mut self) {
+        self.available_midi_ports = self.midi_output.lock().unwrap().rescan();
+        self.last_midi_rescan = Instant::now();
+    }
+
+    /// Whole-pattern transforms, each one undoable edit
+    fn transform_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Transform:");
+            if ui.button("◀ Shift").clicked() {
+                self.sequencer.edit(|grid| grid.shift(-1));
+            }
+            if ui.button("Shift ▶").clicked() {
+                self.sequencer.edit(|grid| grid.shift(1));
+            }
+            if ui.button("▲ Rows").clicked() {
+                self.sequencer.edit(|grid| grid.rotate_rows(-1));
+            }
+            if ui.button("▼ Rows").clicked() {
+                self.sequencer.edit(|grid| grid.rotate_rows(1));
+            }
+            if ui.button("Reverse").clicked() {
+                self.sequencer.edit(Grid::reverse);
+            }
+            if ui.button("Invert").clicked() {
+                self.sequencer.edit(Grid::invert);
+            }
+            if ui.button("Mirror ↔").clicked() {
+                self.sequencer.edit(Grid::mirror_horizontal);
+            }
+            if ui.button("Mirror ↕").clicked() {
+                self.sequencer.edit(Grid::mirror_vertical);
+            }
+
+            ui.add_space(10.0);
+
+            ui.label("Transpose:");
+            if ui.button("−1 deg").clicked() {
+                self.sequencer.edit(|grid| grid.transpose_degrees(-1));
+            }
+            if ui.button("+1 deg").clicked() {
+                self.sequencer.edit(|grid| grid.transpose_degrees(1));
+            }
+            if ui.button("−1 st").clicked() {
+                self.sequencer.transpose_semitones(-1);
+            }
+            if ui.button("+1 st").clicked() {
+                self.sequencer.transpose_semitones(1);
             }
+        });
+    }
+
+    /// The selected cells, or the whole pattern when nothing is selected
+    fn selected_region(&self) -> Region {
+        match self.selection {
+            Some((anchor, corner)) => Region::from_corners(anchor, corner),
+            None => Region::whole(self.sequencer.grid()),
+        }
+    }
+
+    /// Copy (or cut) the selection, to the system clipboard as text too
+    fn copy_selection(&mut self, ctx: &egui::Context, cut: bool) {
+        let region = self.selected_region();
+        let clip = if cut {
+            self.sequencer.edit(|grid| grid.cut_region(&region))
+        } else {
+            self.sequencer.grid().copy_region(&region)
+        };
+        ctx.copy_text(clip.to_text());
+        self.clipboard = Some(clip);
+        self.clipboard_error = None;
+    }
+
+    /// Paste into the selection if it spans several cells, otherwise from the
+    /// selected cell (or the first one) to the end of the pattern
+    fn paste_clip(&mut self, clip: &Clip) {
+        let region = self.selected_region();
+        let target = if self.selection.is_some() && region.cell_count() > 1 {
+            region
+        } else {
+            let grid = self.sequencer.grid();
+            let (x, y) = self.selection.map_or((0, 0), |(anchor, _)| anchor);
+            Region::new(
+                x,
+                y,
+                grid.width().saturating_sub(x),
+                grid.height().saturating_sub(y),
+            )
+        };
+        let mode = self.paste_mode;
+        self.sequencer.edit(|grid| grid.paste(clip, &target, mode));
+    }
+
+    fn paste_text(&mut self, text: &str) {
+        match Clip::from_text(text) {
+            Ok(clip) => {
+                self.paste_clip(&clip);
+                self.clipboard = Some(clip);
+                self.clipboard_error = None;
+            }
+            Err(e) => self.clipboard_error = Some(e),
+        }
+    }
+
+    fn clipboard_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Selection:");
+            let region = self.selected_region();
+            if self.selection.is_some() {
+                ui.label(format!("{}×{}", region.width, region.height));
+                if ui.small_button("Clear").clicked() {
+                    self.selection = None;
+                }
+            } else {
+                ui.label("whole pattern (shift-click steps to select)");
+            }
+
+            ui.add_space(10.0);
+
+            if ui.button("Copy").clicked() {
+                self.copy_selection(ui.ctx(), false);
+            }
+            if ui.button("Cut").clicked() {
+                self.copy_selection(ui.ctx(), true);
+            }
+            let clip = self.clipboard.clone();
+            if ui
+                .add_enabled(clip.is_some(), egui::Button::new("Paste"))
+                .clicked()
+            {
+                if let Some(clip) = clip {
+                    self.paste_clip(&clip);
+                }
+            }
+            egui::ComboBox::from_id_source("paste_mode")
+                .selected_text(self.paste_mode.name())
+                .show_ui(ui, |ui| {
+                    for mode in PasteMode::ALL {
+                        ui.selectable_value(&mut self.paste_mode, mode, mode.name());
+                    }
+                });
+
+            if let Some(error) = &self.clipboard_error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+        });
+    }
+
+    /// Pattern dimensions, applied as one undoable edit
+    fn resize_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let (width, height, mode) = &mut self.resize_draft;
+            ui.label("Size:");
+            ui.add(egui::DragValue::new(width).range(1..=Grid::MAX_WIDTH));
+            ui.label("×");
+            ui.add(egui::DragValue::new(height).range(1..=Grid::MAX_HEIGHT));
+            egui::ComboBox::from_id_source("resize_mode")
+                .selected_text(mode.name())
+                .show_ui(ui, |ui| {
+                    for option in ResizeMode::ALL {
+                        ui.selectable_value(mode, option, option.name());
+                    }
+                });
+
+            let grid = self.sequencer.grid();
+            let unchanged = (*width, *height) == (grid.width(), grid.height());
+            if ui
+                .add_enabled(!unchanged, egui::Button::new("Resize"))
+                .clicked()
+            {
+                let (width, height, mode) = self.resize_draft;
+                self.sequencer.resize(width, height, mode);
+                self.selection = None;
+            }
+        });
+    }
+
+    /// Tempo changes placed on steps of the current pattern's loop
+    fn tempo_changes_ui(&mut self, ui: &mut egui::Ui) {
+        let loop_length = self.sequencer.grid().loop_length();
+        let mut edit = None;
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Tempo changes:");
+            for &(step, mut change) in self.sequencer.grid().tempo_changes() {
+                ui.group(|ui| {
+                    ui.label(format!("Step {}", step + 1));
+                    if tempo_change_editor(ui, &mut change) {
+                        edit = Some((step, Some(change)));
+                    }
+                    if ui.small_button("✕").clicked() {
+                        edit = Some((step, None));
+                    }
+                });
+            }
+
+            let (step, change) = &mut self.tempo_draft;
+            ui.label("at step");
+            ui.add(
+                egui::DragValue::new(step)
+                    .range(0..=loop_length.saturating_sub(1))
+                    .custom_formatter(|n, _| format!("{}", n as usize + 1)),
+            );
+            tempo_change_editor(ui, change);
+            if ui.button("+ Add").clicked() {
+                edit = Some((*step, Some(*change)));
+            }
+        });
+
+        if let Some((step, change)) = edit {
+            self.sequencer.set_tempo_change(step, change);
+        }
+    }
+
+    fn groove_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Groove:");
+            let current = self.sequencer.grid().groove().cloned();
+            let mut selected = None;
+            egui::ComboBox::from_id_source("groove")
+                .selected_text(current.as_ref().map_or("None", |g| g.name()))
+                .show_ui(ui, |ui| {
+                    if ui.selectable_label(current.is_none(), "None").clicked() {
+                        selected = Some(None);
+                    }
+                    for groove in Groove::presets() {
+                        let is_selected = current.as_ref() == Some(&groove);
+                        if ui.selectable_label(is_selected, groove.name()).clicked() {
+                            selected = Some(Some(groove));
+                        }
+                    }
+                });
+            if let Some(groove) = selected {
+                self.sequencer.set_groove(groove);
+            }
+
+            let mut strength = self.sequencer.grid().groove_strength() * 100.0;
+            if ui
+                .add(
+                    egui::Slider::new(&mut strength, 0.0..=100.0)
+                        .suffix("%")
+                        .text("strength"),
+                )
+                .changed()
+            {
+                self.sequencer.set_groove_strength(strength / 100.0);
+            }
+
+            ui.add_space(20.0);
+
+            ui.add(
+                egui::TextEdit::singleline(&mut self.groove_path)
+                    .hint_text("groove.mid")
+                    .desired_width(160.0),
+            );
+            if ui.button("Load MIDI groove").clicked() {
+                let steps = self.sequencer.grid().step_count().clamp(1, 16);
+                match Groove::from_midi_file(&self.groove_path, steps) {
+                    Ok(groove) => {
+                        self.sequencer.set_groove(Some(groove));
+                        self.groove_error = None;
+                    }
+                    Err(e) => self.groove_error = Some(e),
+                }
+            }
+            if let Some(error) = &self.groove_error {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+        });
+    }
+
+    fn song_ui(&mut self, ui: &mut egui::Ui, current_pattern: usize, is_playing: bool) {
+        let pattern_count = self.sequencer.bank().len();
+        let bpm = self.sequencer.bpm();
+        // Arrangement changes made here go into the undo history
+        self.sequencer.edit_song(|song| {
+            ui.horizontal(|ui| {
+                let mut enabled = song.is_enabled();
+                if ui.checkbox(&mut enabled, "Song mode").changed() {
+                    song.set_enabled(enabled);
+                }
+                if ui.button("+ Add current pattern").clicked() {
+                    song.push(SongEntry::new(current_pattern, 1));
+                }
+                if ui.button("⏮ Rewind").clicked() {
+                    song.reset();
+                }
+
+                let mut looping = song.loop_range().is_some();
+                if ui.checkbox(&mut looping, "Loop").changed() {
+                    if looping && !song.is_empty() {
+                        let last = song.len() - 1;
+                        song.set_loop(0, last);
+                    } else {
+                        song.clear_loop();
+                    }
+                }
+                if let Some((mut start, mut end)) = song.loop_range() {
+                    let last = song.len().saturating_sub(1);
+                    ui.label("from");
+                    let start_changed = ui
+                        .add(
+                            egui::DragValue::new(&mut start)
+                                .range(0..=last)
+                                .custom_formatter(|n, _| format!("{}", n as usize + 1)),
+                        )
+                        .changed();
+                    ui.label("to");
+                    let end_changed = ui
+                        .add(
+                            egui::DragValue::new(&mut end)
+                                .range(0..=last)
+                                .custom_formatter(|n, _| format!("{}", n as usize + 1)),
+                        )
+                        .changed();
+                    if start_changed || end_changed {
+                        song.set_loop(start.min(end), end.max(start));
+                    }
+                }
+            });
+
+            let position = song.position();
+            let pending_jump = song.pending_jump();
+            let mut jump_to = None;
+            let mut remove = None;
+            ui.horizontal_wrapped(|ui| {
+                for index in 0..song.len() {
+                    let is_current = song.is_enabled() && position.entry == index;
+                    let entry = song.entry_mut(index).unwrap();
+                    ui.group(|ui| {
+                        let label = if is_current {
+                            format!("● {}", index + 1)
+                        } else if pending_jump == Some(index) {
+                            format!("→ {}", index + 1)
+                        } else {
+                            format!("{}", index + 1)
+                        };
+                        ui.label(label);
+                        ui.label("P");
+                        ui.add(
+                            egui::DragValue::new(&mut entry.pattern)
+                                .range(0..=pattern_count - 1)
+                                .custom_formatter(|n, _| format!("{}", n as usize + 1)),
+                        );
+                        ui.label("×");
+                        ui.add(egui::DragValue::new(&mut entry.repeats).range(1..=64));
+                        if is_current {
+                            ui.label(format!("({}/{})", position.repeat + 1, entry.repeats));
+                        }
+                        let mut has_tempo = entry.tempo.is_some();
+                        if ui.checkbox(&mut has_tempo, "♩").changed() {
+                            entry.tempo = has_tempo.then(|| TempoChange::new(bpm));
+                        }
+                        if let Some(tempo) = &mut entry.tempo {
+                            tempo_change_editor(ui, tempo);
+                        }
+                        if ui.small_button("Jump").clicked() {
+                            jump_to = Some(index);
+                        }
+                        if ui.small_button("✕").clicked() {
+                            remove = Some(index);
+                        }
+                    });
+                }
+            });
+
+            if let Some(index) = jump_to {
+                if is_playing {
+                    song.jump_to(index);
+                } else {
+                    song.seek(index);
+                }
+            }
+            if let Some(index) = remove {
+                song.remove(index);
+            }
+        });
+    }
+
+    /// Export the arrangement's notes and tempo changes for use in a DAW
+    fn export_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.export_path)
+                    .hint_text("song.mid")
+                    .desired_width(160.0),
+            );
+            if ui.button("Export MIDI file").clicked() {
+                self.export_status = Some(self.sequencer.export_smf(&self.export_path));
+            }
+            match &self.export_status {
+                Some(Ok(())) => {
+                    ui.label("Exported");
+                }
+                Some(Err(error)) => {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+                None => {}
+            }
+        });
+    }
+
+    fn undo(&mut self) {
+        if self.sequencer.undo() {
+            self.sync_after_history();
+        }
+    }
+
+    fn redo(&mut self) {
+        if self.sequencer.redo() {
+            self.sync_after_history();
         }
     }
 
+    /// Undo and redo can change the tempo and root, which live outside the grid
+    fn sync_after_history(&mut self) {
+        self.playback_engine.set_bpm(self.sequencer.bpm());
+        self.note_input = midi_note_name(self.sequencer.note());
+        self.note_input_error = false;
+    }
+
+    fn tap_tempo(&mut self) {
+        if let Some(bpm) = self.sequencer.tap_tempo(Instant::now()) {
+            self.playback_engine.set_bpm(bpm);
+        }
+    }
+
+    fn metronome_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let mut metronome = self.playback_engine.metronome();
+            let mut changed = ui
+                .toggle_value(&mut metronome.enabled, "Metronome")
+                .changed();
+
+            ui.label("Count-in:");
+            let count_in = match metronome.count_in_bars() {
+                0 => "Off".to_string(),
+                1 => "1 bar".to_string(),
+                bars => format!("{} bars", bars),
+            };
+            egui::ComboBox::from_id_source("count_in")
+                .selected_text(count_in)
+                .show_ui(ui, |ui| {
+                    for (bars, label) in [(0, "Off"), (1, "1 bar"), (2, "2 bars")] {
+                        let is_selected = metronome.count_in_bars() == bars;
+                        if ui.selectable_label(is_selected, label).clicked() {
+                            metronome.set_count_in_bars(bars);
+                            changed = true;
+                        }
+                    }
+                });
+            if changed {
+                self.playback_engine.set_metronome(metronome);
+            }
+
+            ui.add_space(20.0);
+
+            // Send the click to a MIDI note instead of the built-in sound
+            let mut midi_output = self.midi_output.lock().unwrap();
+            let mut click_note = midi_output.click_note();
+            let mut to_midi = click_note.is_some();
+            let mut note = click_note.unwrap_or(DEFAULT_CLICK_NOTE);
+            if ui.checkbox(&mut to_midi, "Click to MIDI note").changed() {
+                click_note = to_midi.then_some(note);
+            }
+            if to_midi
+                && ui
+                    .add(egui::DragValue::new(&mut note).range(0..=127))
+                    .changed()
+            {
+                click_note = Some(note);
+            }
+            if to_midi {
+                ui.label(midi_note_name(note));
+            }
+            midi_output.set_click_note(click_note);
+            self.audio_output
+                .borrow_mut()
+                .set_click_enabled(click_note.is_none());
+        });
+    }
+
     fn start_playback(&mut self) {
-        let grid = self.sequencer.grid();
+        self.musical_position = MusicalPosition::default();
         self.playback_engine.start(
             self.sequencer.bpm(),
-            grid.width(),
-            grid.height(),
             self.sequencer.grid_state().clone(),
-            self.sequencer.note(),
+            self.sequencer.song_state().clone(),
+            self.sequencer.key_state().clone(),
         );
     }
 
     fn stop_playback(&mut self) {
         self.playback_engine.stop();
-        self.audio_output.stop_note();
-        let _ = self.midi_output.send_note_off(self.sequencer.note());
+        let _ = self
+            .dispatcher
+            .dispatch(&PlaybackEvent::NoteOff(self.sequencer.note()));
     }
 }
 
@@ -103,40 +809,118 @@ impl eframe::App for SequencerApp {
 
         self.handle_playback_events();
 
+        if self.last_midi_rescan.elapsed() >= MIDI_RESCAN_INTERVAL {
+            self.rescan_midi_ports();
+        }
+
+        // Drags are grouped into a single undo step
+        let pointer_down = ctx.input(|i| i.pointer.any_down());
+        if pointer_down && !self.sequencer.is_edit_group_open() {
+            self.sequencer.begin_edit_group();
+        } else if !pointer_down && self.sequencer.is_edit_group_open() {
+            self.sequencer.end_edit_group();
+        }
+
+        let redo_shortcut = egui::KeyboardShortcut::new(
+            egui::Modifiers::COMMAND | egui::Modifiers::SHIFT,
+            egui::Key::Z,
+        );
+        let undo_shortcut = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Z);
+        if !ctx.wants_keyboard_input() {
+            // Check the longer shortcut first so Ctrl+Shift+Z isn't taken as Ctrl+Z
+            if ctx.input_mut(|i| i.consume_shortcut(&redo_shortcut)) {
+                self.redo();
+            } else if ctx.input_mut(|i| i.consume_shortcut(&undo_shortcut)) {
+                self.undo();
+            }
+        }
+
+        // Ctrl+C/X/V copy, cut and paste the selection as text; Escape deselects
+        if !ctx.wants_keyboard_input() {
+            let events = ctx.input(|i| i.events.clone());
+            for event in events {
+                match event {
+                    egui::Event::Copy => self.copy_selection(ctx, false),
+                    egui::Event::Cut => self.copy_selection(ctx, true),
+                    egui::Event::Paste(text) => self.paste_text(&text),
+                    egui::Event::Key {
+                        key: egui::Key::Escape,
+                        pressed: true,
+                        ..
+                    } => {
+                        self.selection = None;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        // T taps the tempo, holding the left/right arrows nudges it
+        let mut nudge = 0.0;
+        if !ctx.wants_keyboard_input() {
+            let (tap, slower, faster) = ctx.input(|i| {
+                (
+                    i.key_pressed(egui::Key::T),
+                    i.key_down(egui::Key::ArrowLeft),
+                    i.key_down(egui::Key::ArrowRight),
+                )
+            });
+            if tap {
+                self.tap_tempo();
+            }
+            if slower {
+                nudge -= TEMPO_NUDGE_AMOUNT;
+            }
+            if faster {
+                nudge += TEMPO_NUDGE_AMOUNT;
+            }
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.heading("SQNC - Step Sequencer");
             ui.add_space(10.0);
 
             // MIDI Port Selection
             let mut selected_port_changed = None;
+            let mut refresh_clicked = false;
+            let selected_port = self
+                .midi_output
+                .lock()
+                .unwrap()
+                .port_name()
+                .map(str::to_string);
             ui.horizontal(|ui| {
                 ui.label("MIDI Output:");
                 if self.available_midi_ports.is_empty() {
                     ui.label("No MIDI ports available");
                 } else {
                     egui::ComboBox::from_label("")
-                        .selected_text(
-                            self.selected_port
-                                .map(|i| self.available_midi_ports[i].as_str())
-                                .unwrap_or("Select port..."),
-                        )
+                        .selected_text(selected_port.as_deref().unwrap_or("Select port..."))
                         .show_ui(ui, |ui| {
-                            for (i, port_name) in self.available_midi_ports.iter().enumerate() {
+                            for port_name in &self.available_midi_ports {
                                 if ui
-                                    .selectable_label(self.selected_port == Some(i), port_name)
+                                    .selectable_label(
+                                        selected_port.as_deref() == Some(port_name.as_str()),
+                                        port_name,
+                                    )
                                     .clicked()
                                 {
-                                    selected_port_changed = Some(i);
+                                    selected_port_changed = Some(port_name.clone());
                                 }
                             }
                         });
                 }
+                if ui.button("⟳ Refresh").clicked() {
+                    refresh_clicked = true;
+                }
             });
 
-            if let Some(port_idx) = selected_port_changed {
-                if let Ok(()) = self.midi_output.connect(port_idx) {
-                    self.selected_port = Some(port_idx);
-                }
+            if let Some(port_name) = selected_port_changed {
+                let _ = self.midi_output.lock().unwrap().connect_by_name(&port_name);
+            }
+
+            if refresh_clicked {
+                self.rescan_midi_ports();
             }
 
             ui.add_space(10.0);
@@ -155,80 +939,492 @@ impl eframe::App for SequencerApp {
                     }
                 }
 
+                let history = self.sequencer.history();
+                let (can_undo, can_redo) = (history.can_undo(), history.can_redo());
+                if ui
+                    .add_enabled(can_undo, egui::Button::new("↶ Undo"))
+                    .clicked()
+                {
+                    self.undo();
+                }
+                if ui
+                    .add_enabled(can_redo, egui::Button::new("↷ Redo"))
+                    .clicked()
+                {
+                    self.redo();
+                }
+
+                let mut fill = self.playback_engine.is_fill();
+                if ui.toggle_value(&mut fill, "Fill").changed() {
+                    self.playback_engine.set_fill(fill);
+                }
+
                 ui.add_space(20.0);
 
                 ui.label("BPM:");
                 let mut bpm = self.sequencer.bpm();
                 if ui
-                    .add(egui::Slider::new(&mut bpm, 40.0..=240.0).step_by(1.0))
+                    .add(
+                        egui::Slider::new(&mut bpm, Sequencer::MIN_BPM..=Sequencer::MAX_BPM)
+                            .logarithmic(true)
+                            .max_decimals(2),
+                    )
                     .changed()
                 {
                     self.sequencer.set_bpm(bpm);
+                    self.playback_engine.set_bpm(self.sequencer.bpm());
+                }
+
+                if ui.button("Tap").clicked() {
+                    self.tap_tempo();
+                }
+                if ui.button("◀").is_pointer_button_down_on() {
+                    nudge -= TEMPO_NUDGE_AMOUNT;
+                }
+                if ui.button("▶").is_pointer_button_down_on() {
+                    nudge += TEMPO_NUDGE_AMOUNT;
                 }
 
                 ui.add_space(20.0);
 
-                ui.label("Note:");
+                ui.label("Root:");
                 let mut note = self.sequencer.note();
                 if ui
                     .add(egui::Slider::new(&mut note, 0..=127).step_by(1.0))
                     .changed()
                 {
                     self.sequencer.set_note(note);
+                    self.note_input = midi_note_name(note);
+                    self.note_input_error = false;
                 }
-                ui.label(format!("({})", midi_note_name(note)));
-            });
 
-            ui.add_space(20.0);
+                // Typed note entry, applied on Enter or when focus leaves
+                let text_color = if self.note_input_error {
+                    egui::Color32::RED
+                } else {
+                    ui.visuals().text_color()
+                };
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut self.note_input)
+                        .desired_width(50.0)
+                        .text_color(text_color),
+                );
+                if response.lost_focus() {
+                    self.note_input_error = self.sequencer.set_note_name(&self.note_input).is_err();
+                    if !self.note_input_error {
+                        self.note_input = midi_note_name(self.sequencer.note());
+                    }
+                }
 
-            // Step grid (16 steps in 2 rows of 8)
-            ui.label("Steps:");
-            ui.add_space(5.0);
+                ui.add_space(20.0);
 
+                let mut selected_scale = None;
+                egui::ComboBox::from_id_source("scale")
+                    .selected_text(self.sequencer.key().scale().name())
+                    .show_ui(ui, |ui| {
+                        for scale in Scale::PRESETS {
+                            let is_selected = *self.sequencer.key().scale() == scale;
+                            if ui.selectable_label(is_selected, scale.name()).clicked() {
+                                selected_scale = Some(scale);
+                            }
+                        }
+                    });
+                if let Some(scale) = selected_scale {
+                    self.sequencer.set_scale(scale);
+                }
+            });
+
+            ui.add_space(10.0);
+
+            // Pattern bank: click to switch (queued while playing), right-click for copy/clear
             let is_playing = self.playback_engine.is_running();
+            let current_pattern = self.sequencer.bank().current_index();
+            let queued_pattern = self.sequencer.bank().queued();
+            let mut pattern_action = None;
+            ui.horizontal(|ui| {
+                ui.label("Pattern:");
+                for slot in 0..self.sequencer.bank().len() {
+                    let fill = if slot == current_pattern {
+                        egui::Color32::from_rgb(100, 200, 100)
+                    } else if queued_pattern == Some(slot) {
+                        egui::Color32::from_rgb(200, 160, 60)
+                    } else {
+                        egui::Color32::from_rgb(40, 40, 40)
+                    };
+                    let response = ui.add(
+                        egui::Button::new(format!("{}", slot + 1))
+                            .min_size(egui::vec2(28.0, 24.0))
+                            .fill(fill),
+                    );
+                    if response.clicked() {
+                        pattern_action = Some(PatternAction::Switch(slot));
+                    }
+                    response.context_menu(|ui| {
+                        if ui.button("Copy current here").clicked() {
+                            pattern_action = Some(PatternAction::CopyTo(slot));
+                            ui.close_menu();
+                        }
+                        if ui.button("Clear").clicked() {
+                            pattern_action = Some(PatternAction::Clear(slot));
+                            ui.close_menu();
+                        }
+                    });
+                }
+            });
+
+            match pattern_action {
+                Some(PatternAction::Switch(slot)) if is_playing => {
+                    self.sequencer.queue_pattern(slot)
+                }
+                Some(PatternAction::Switch(slot)) => self.sequencer.select_pattern(slot),
+                Some(PatternAction::CopyTo(slot)) => {
+                    self.sequencer.copy_pattern(current_pattern, slot)
+                }
+                Some(PatternAction::Clear(slot)) => self.sequencer.clear_pattern(slot),
+                None => {}
+            }
 
             ui.horizontal(|ui| {
-                for i in 1..8 {
-                    ui.vertical(|ui| {
-                        for j in 1..8 {
-                            let is_current = is_playing && self.current_visual_step == i;
-                            let number = i * j
-                            let button_text = if is_current {
-                                format!("● {}", number)
-                            } else {
-                                format!("{}", number)
-                            };
-
-                            let step_enabled = self.sequencer.grid_mut().get(i, j);
-
-                            let button = egui::Button::new(button_text)
-                                .min_size(egui::vec2(80.0, 60.0))
-                                .fill(if is_current {
-                                    egui::Color32::from_rgb(100, 200, 100)
-                                } else if step_enabled {
-                                    egui::Color32::from_rgb(60, 60, 200)
-                                } else {
-                                    egui::Color32::from_rgb(40, 40, 40)
-                                });
-
-                            if ui.add(button).clicked() {
-                                self.sequencer.grid_mut().toggle(i, j);
-                                self.sequencer.update_grid_state();
+                ui.label("Direction:");
+                let mut selected_direction = None;
+                egui::ComboBox::from_id_source("direction")
+                    .selected_text(self.sequencer.grid().direction().name())
+                    .show_ui(ui, |ui| {
+                        for direction in Direction::ALL {
+                            let is_selected = self.sequencer.grid().direction() == direction;
+                            if ui.selectable_label(is_selected, direction.name()).clicked() {
+                                selected_direction = Some(direction);
                             }
                         }
                     });
+                if let Some(direction) = selected_direction {
+                    self.sequencer.set_direction(direction);
+                }
+
+                ui.add_space(20.0);
+
+                // Track mode gives each row its own length and direction
+                let mut track_mode = self.sequencer.grid().track_mode();
+                if ui.checkbox(&mut track_mode, "Tracks").changed() {
+                    self.sequencer.set_track_mode(track_mode);
+                    self.track_positions.clear();
+                }
+
+                ui.add_space(20.0);
+
+                ui.label("Path:");
+                let mut selected_traversal = None;
+                egui::ComboBox::from_id_source("traversal")
+                    .selected_text(self.sequencer.grid().traversal().name())
+                    .show_ui(ui, |ui| {
+                        for traversal in Traversal::PRESETS {
+                            let is_selected = *self.sequencer.grid().traversal() == traversal;
+                            if ui.selectable_label(is_selected, traversal.name()).clicked() {
+                                selected_traversal = Some(traversal);
+                            }
+                        }
+                    });
+                if let Some(traversal) = selected_traversal {
+                    self.sequencer.set_traversal(traversal);
+                }
+
+                match self.drawn_path.take() {
+                    None => {
+                        if ui.button("✎ Draw path").clicked() {
+                            self.drawn_path = Some(Vec::new());
+                        }
+                    }
+                    Some(path) => {
+                        if ui.button("✔ Done").clicked() {
+                            if !path.is_empty() {
+                                self.sequencer.set_traversal(Traversal::Custom(path));
+                            }
+                        } else if !ui.button("✕ Cancel").clicked() {
+                            self.drawn_path = Some(path);
+                        }
+                    }
+                }
+            });
+
+            self.metronome_ui(ui);
+
+            ui.horizontal(|ui| {
+                ui.label("Time:");
+                let mut selected_signature = None;
+                let signature = self.sequencer.grid().time_signature();
+                egui::ComboBox::from_id_source("time_signature")
+                    .selected_text(signature.name())
+                    .show_ui(ui, |ui| {
+                        for preset in TimeSignature::PRESETS {
+                            let is_selected = signature == preset;
+                            if ui.selectable_label(is_selected, preset.name()).clicked() {
+                                selected_signature = Some(preset);
+                            }
+                        }
+                    });
+                if let Some(signature) = selected_signature {
+                    self.sequencer.set_time_signature(signature);
+                }
+
+                ui.add_space(20.0);
+
+                ui.label("Step:");
+                let mut selected_resolution = None;
+                let resolution = self.sequencer.grid().resolution();
+                egui::ComboBox::from_id_source("resolution")
+                    .selected_text(resolution.name())
+                    .show_ui(ui, |ui| {
+                        for preset in Resolution::ALL {
+                            let is_selected = resolution == preset;
+                            if ui.selectable_label(is_selected, preset.name()).clicked() {
+                                selected_resolution = Some(preset);
+                            }
+                        }
+                    });
+                if let Some(resolution) = selected_resolution {
+                    self.sequencer.set_resolution(resolution);
+                }
+
+                if is_playing {
+                    ui.add_space(20.0);
+                    ui.monospace(self.musical_position.name());
                 }
             });
 
+            self.resize_ui(ui);
+            self.transform_ui(ui);
+            self.clipboard_ui(ui);
+            self.groove_ui(ui);
+            self.tempo_changes_ui(ui);
+
+            ui.add_space(10.0);
+
+            self.song_ui(ui, current_pattern, is_playing);
+            self.export_ui(ui);
+
+            ui.add_space(20.0);
+
+            // Step grid
+            if self.drawn_path.is_some() {
+                ui.label("Steps (click cells in the order they should play):");
+            } else {
+                ui.label("Steps:");
+            }
+            ui.add_space(5.0);
+
+            let spacing = ui.spacing().item_spacing.x;
+            let columns = self.sequencer.grid().width().max(1) as f32;
+            let cell_width = (ui.available_width() / columns - spacing).clamp(MIN_CELL_WIDTH, 80.0);
+            let cell_size = egui::vec2(cell_width, cell_width * 0.75);
+
+            // Cells shrink to fit the window; grids too large even then scroll
+            egui::ScrollArea::both()
+                .id_source("step_grid")
+                .max_height(GRID_MAX_HEIGHT)
+                .show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        let grid = self.sequencer.grid();
+                        let track_mode = grid.track_mode();
+                        let playhead_cell = grid.cell_at(self.current_visual_step);
+                        let (width, height) = (grid.width(), grid.height());
+                        let selection = self.selection.map(|(a, b)| Region::from_corners(a, b));
+                        let order = match &self.drawn_path {
+                            Some(path) => path.clone(),
+                            None => grid.traversal().path(grid.width(), grid.height()),
+                        };
+
+                        for i in 0..width {
+                            ui.vertical(|ui| {
+                                for j in 0..height {
+                                    let is_current = is_playing
+                                        && if track_mode {
+                                            self.track_positions.get(j) == Some(&i)
+                                        } else {
+                                            playhead_cell == Some((i, j))
+                                        };
+                                    let number = order
+                                        .iter()
+                                        .position(|&cell| cell == (i, j))
+                                        .map(|n| (n + 1).to_string())
+                                        .unwrap_or_default();
+                                    let step = self.sequencer.grid().step(i, j);
+                                    let step_enabled = step.active;
+                                    let note = self.sequencer.note_at(i, j);
+                                    let mut note_name = midi_note_name(note);
+                                    if step.probability < 100 {
+                                        note_name.push_str(&format!(" {}%", step.probability));
+                                    }
+                                    if step.tie {
+                                        note_name.push_str(" ⁀");
+                                    } else if step.slide {
+                                        note_name.push_str(" ↗");
+                                    }
+                                    if step.ratchet > 1 {
+                                        note_name.push_str(&format!(" ×{}", step.ratchet));
+                                    }
+                                    if step.condition != Condition::Always {
+                                        note_name.push_str(&format!(" {}", step.condition.name()));
+                                    }
+                                    let button_text = if is_current {
+                                        format!("● {}\n{}", number, note_name)
+                                    } else {
+                                        format!("{}\n{}", number, note_name)
+                                    };
+
+                                    let selected = selection.is_some_and(|s| s.contains(i, j));
+                                    let button = egui::Button::new(button_text)
+                                        .min_size(cell_size)
+                                        .stroke(if selected {
+                                            egui::Stroke::new(2.0, egui::Color32::YELLOW)
+                                        } else {
+                                            egui::Stroke::NONE
+                                        })
+                                        .fill(if is_current {
+                                            egui::Color32::from_rgb(100, 200, 100)
+                                        } else if step_enabled {
+                                            egui::Color32::from_rgb(60, 60, 200)
+                                        } else {
+                                            egui::Color32::from_rgb(40, 40, 40)
+                                        });
+
+                                    let response = ui.add(button);
+                                    let shift = ui.input(|input| input.modifiers.shift);
+                                    if response.clicked() && shift {
+                                        // Shift-click starts a selection, then extends it
+                                        let anchor = self.selection.map_or((i, j), |(a, _)| a);
+                                        self.selection = Some((anchor, (i, j)));
+                                    } else if let (true, Some(path)) =
+                                        (response.clicked(), &mut self.drawn_path)
+                                    {
+                                        path.push((i, j));
+                                    } else if response.clicked() {
+                                        self.sequencer.edit(|grid| grid.toggle(i, j));
+                                    }
+
+                                    // Right-click to edit the step's degree, probability, condition
+                                    response.context_menu(|ui| {
+                                        self.sequencer.edit(|grid| step_menu(ui, grid, i, j));
+                                    });
+                                }
+                            });
+                        }
+                    });
+                });
+
+            ui.add_space(10.0);
+
+            // Euclidean generator per row, plus track length and direction in track mode
+            ui.label("Euclidean:");
+            let width = self.sequencer.grid().width();
+            let track_mode = self.sequencer.grid().track_mode();
+            self.euclid_settings
+                .resize(self.sequencer.grid().height(), (0, 0));
+            let mut apply_row = None;
+            let mut row_transform = None;
+            let mut track_edit = None;
+            for (row, (hits, rotation)) in self.euclid_settings.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("Row {}", row + 1));
+                    ui.label("Hits:");
+                    ui.add(egui::DragValue::new(hits).range(0..=width));
+                    ui.label("Rotate:");
+                    ui.add(egui::DragValue::new(rotation).range(0..=width.saturating_sub(1)));
+                    if ui.button("Apply").clicked() {
+                        apply_row = Some((row, *hits, *rotation));
+                    }
+                    if ui.small_button("◀").clicked() {
+                        row_transform = Some((row, RowTransform::Shift(-1)));
+                    }
+                    if ui.small_button("▶").clicked() {
+                        row_transform = Some((row, RowTransform::Shift(1)));
+                    }
+                    if ui.small_button("⇄").on_hover_text("Reverse").clicked() {
+                        row_transform = Some((row, RowTransform::Reverse));
+                    }
+                    if ui.small_button("◑").on_hover_text("Invert").clicked() {
+                        row_transform = Some((row, RowTransform::Invert));
+                    }
+
+                    let Some(track) = self.sequencer.grid().track(row).copied() else {
+                        return;
+                    };
+                    if !track_mode {
+                        return;
+                    }
+                    ui.add_space(20.0);
+                    ui.label("Length:");
+                    let mut length = track.length();
+                    if ui
+                        .add(egui::DragValue::new(&mut length).range(1..=width))
+                        .changed()
+                    {
+                        track_edit = Some((row, length, track.direction(), track.rate()));
+                    }
+                    egui::ComboBox::from_id_source(("track_direction", row))
+                        .selected_text(track.direction().name())
+                        .show_ui(ui, |ui| {
+                            for direction in Direction::ALL {
+                                let is_selected = track.direction() == direction;
+                                if ui.selectable_label(is_selected, direction.name()).clicked() {
+                                    track_edit =
+                                        Some((row, track.length(), direction, track.rate()));
+                                }
+                            }
+                        });
+                    ui.label("Rate:");
+                    egui::ComboBox::from_id_source(("track_rate", row))
+                        .selected_text(track.rate().name())
+                        .show_ui(ui, |ui| {
+                            for rate in Rate::PRESETS {
+                                let is_selected = track.rate() == rate;
+                                if ui.selectable_label(is_selected, rate.name()).clicked() {
+                                    track_edit =
+                                        Some((row, track.length(), track.direction(), rate));
+                                }
+                            }
+                        });
+                });
+            }
+
+            if let Some((row, length, direction, rate)) = track_edit {
+                self.sequencer.set_track_length(row, length);
+                self.sequencer.set_track_direction(row, direction);
+                self.sequencer.set_track_rate(row, rate);
+            }
+
+            if let Some((row, transform)) = row_transform {
+                self.sequencer.edit(|grid| match transform {
+                    RowTransform::Shift(offset) => grid.shift_row(row, offset),
+                    RowTransform::Reverse => grid.reverse_row(row),
+                    RowTransform::Invert => grid.invert_row(row),
+                });
+            }
+
+            if let Some((row, hits, rotation)) = apply_row {
+                self.sequencer
+                    .edit(|grid| grid.fill_euclidean_row(row, hits, rotation));
+            }
+
             // Info
             ui.separator();
-            ui.label("Click steps to enable/disable them");
-            if !self.midi_output.is_connected() {
-                ui.colored_label(
-                    egui::Color32::YELLOW,
-                    "⚠ No MIDI output connected - audio playback only",
-                );
+            ui.label("Click steps to enable/disable them, right-click to set their scale degree");
+            ui.label("T taps the tempo, hold ← / → to nudge it slower or faster");
+            ui.label("Ctrl+Z undoes an edit, Ctrl+Shift+Z redoes it");
+            let midi_output = self.midi_output.lock().unwrap();
+            if !midi_output.is_connected() {
+                let message = match midi_output.port_name() {
+                    Some(name) => {
+                        format!(
+                            "⚠ MIDI port '{}' unplugged - waiting for it to return",
+                            name
+                        )
+                    }
+                    None => "⚠ No MIDI output connected - audio playback only".to_string(),
+                };
+                ui.colored_label(egui::Color32::YELLOW, message);
             }
         });
+
+        self.playback_engine.set_tempo_nudge(nudge);
     }
 }
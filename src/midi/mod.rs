@@ -1,13 +1,26 @@
 /// MIDI output using midir
+pub mod scale;
+pub mod smf;
+
+use crate::output::EventSink;
+use crate::sequencer::playback::PlaybackEvent;
 use midir::{MidiOutput, MidiOutputConnection};
 
 pub struct MidiOutputDevice {
     connection: Option<MidiOutputConnection>,
+    port_name: Option<String>,
+    click_note: Option<u8>,
+    click_sounding: bool,
 }
 
 impl MidiOutputDevice {
     pub fn new() -> Self {
-        Self { connection: None }
+        Self {
+            connection: None,
+            port_name: None,
+            click_note: None,
+            click_sounding: false,
+        }
     }
 
     pub fn available_ports() -> Vec<String> {
@@ -30,15 +43,53 @@ impl MidiOutputDevice {
         let port = ports
             .get(port_index)
             .ok_or_else(|| "Invalid port index".to_string())?;
+        let name = midi_out
+            .port_name(port)
+            .map_err(|e| format!("Failed to read port name: {}", e))?;
         
         let connection = midi_out
             .connect(port, "sqnc")
             .map_err(|e| format!("Failed to connect: {}", e))?;
         
         self.connection = Some(connection);
+        self.port_name = Some(name);
         Ok(())
     }
 
+    /// Connect to a port by name, which stays stable across hot-plugs
+    /// unlike the port index. Falls back to a case-insensitive substring match.
+    pub fn connect_by_name(&mut self, name: &str) -> Result<(), String> {
+        let ports = Self::available_ports();
+        let port_index =
+            find_port(&ports, name).ok_or_else(|| format!("No MIDI port named '{}'", name))?;
+        self.connect(port_index)
+    }
+
+    /// Name of the selected port, kept while the device is unplugged
+    pub fn port_name(&self) -> Option<&str> {
+        self.port_name.as_deref()
+    }
+
+    /// Re-read the port list, dropping the connection if the selected device
+    /// vanished and reconnecting once it reappears. Returns the current ports.
+    pub fn rescan(&mut self) -> Vec<String> {
+        let ports = Self::available_ports();
+
+        if let Some(name) = self.port_name.clone() {
+            // A replugged device can come back under a new client id, so match
+            // the same way as `connect_by_name` and reconnect if the name moved
+            match find_port(&ports, &name) {
+                None => self.connection = None,
+                Some(index) if self.connection.is_none() || ports[index] != name => {
+                    let _ = self.connect(index);
+                }
+                Some(_) => {}
+            }
+        }
+
+        ports
+    }
+
     pub fn is_connected(&self) -> bool {
         self.connection.is_some()
     }
@@ -59,8 +110,41 @@ impl MidiOutputDevice {
         Ok(())
     }
 
+    /// Send metronome clicks as this note (accented downbeats at full
+    /// velocity), or `None` to leave the click to the audio engine
+    pub fn set_click_note(&mut self, note: Option<u8>) {
+        self.click_note = note.map(|n| n.min(127));
+    }
+
+    pub fn click_note(&self) -> Option<u8> {
+        self.click_note
+    }
+
+    fn click(&mut self, accent: bool) -> Result<(), String> {
+        let Some(note) = self.click_note else {
+            return Ok(());
+        };
+        // Never leave a click hanging if its release was lost on stop
+        if self.click_sounding {
+            self.send_note_off(note)?;
+        }
+        self.click_sounding = true;
+        self.send_note_on(note, if accent { 127 } else { 90 })
+    }
+
+    fn release_click(&mut self) -> Result<(), String> {
+        match self.click_note {
+            Some(note) if self.click_sounding => {
+                self.click_sounding = false;
+                self.send_note_off(note)
+            }
+            _ => Ok(()),
+        }
+    }
+
     pub fn disconnect(&mut self) {
         self.connection = None;
+        self.port_name = None;
     }
 }
 
@@ -70,6 +154,57 @@ impl Default for MidiOutputDevice {
     }
 }
 
+impl EventSink for MidiOutputDevice {
+    fn handle_event(&mut self, event: &PlaybackEvent) -> Result<(), String> {
+        match *event {
+            // Slides rely on the overlap between this note-on and the previous note-off
+            PlaybackEvent::NoteOn(note, velocity) | PlaybackEvent::SlideTo(note, velocity) => {
+                self.send_note_on(note, velocity)
+            }
+            PlaybackEvent::NoteOff(note) => self.send_note_off(note),
+            PlaybackEvent::Click(accent) => self.click(accent),
+            PlaybackEvent::ClickOff => self.release_click(),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn find_port(ports: &[String], name: &str) -> Option<usize> {
+    ports
+        .iter()
+        .position(|p| p == name)
+        .or_else(|| {
+            let needle = name.to_lowercase();
+            ports
+                .iter()
+                .position(|p| p.to_lowercase().contains(&needle))
+        })
+        .or_else(|| {
+            let needle = strip_port_id(name);
+            ports.iter().position(|p| strip_port_id(p) == needle)
+        })
+}
+
+/// A port name without the trailing ALSA "client:port" id, which changes
+/// when a device is replugged
+fn strip_port_id(name: &str) -> &str {
+    match name.rsplit_once(' ') {
+        Some((base, id))
+            if id.split_once(':').is_some_and(|(client, port)| {
+                !client.is_empty()
+                    && !port.is_empty()
+                    && client
+                        .chars()
+                        .chain(port.chars())
+                        .all(|c| c.is_ascii_digit())
+            }) =>
+        {
+            base
+        }
+        _ => name,
+    }
+}
+
 pub fn midi_note_name(note: u8) -> String {
     let note_names = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
     let octave = (note / 12) as i32 - 1;
@@ -77,3 +212,118 @@ pub fn midi_note_name(note: u8) -> String {
     format!("{}{}", note_names[note_index], octave)
 }
 
+/// Octave number of middle C (MIDI 60) used by `midi_note_name`
+pub const DEFAULT_MIDDLE_C_OCTAVE: i32 = 4;
+
+/// Parse a note name such as "C4", "F#2", "Bb-1", "c##5" or "Ebb3" into a
+/// MIDI note number, using the C4 = 60 convention of `midi_note_name`.
+pub fn parse_note_name(name: &str) -> Result<u8, String> {
+    parse_note_name_with_octave(name, DEFAULT_MIDDLE_C_OCTAVE)
+}
+
+/// Parse a note name where middle C is written in `middle_c_octave`
+/// (e.g. 3 for the Yamaha convention). A missing octave means middle C's.
+pub fn parse_note_name_with_octave(name: &str, middle_c_octave: i32) -> Result<u8, String> {
+    let name = name.trim();
+    let mut chars = name.chars().peekable();
+
+    let pitch_class = match chars.next().map(|c| c.to_ascii_uppercase()) {
+        Some('C') => 0,
+        Some('D') => 2,
+        Some('E') => 4,
+        Some('F') => 5,
+        Some('G') => 7,
+        Some('A') => 9,
+        Some('B') => 11,
+        _ => return Err(format!("Invalid note name '{}'", name)),
+    };
+
+    let mut accidental: i32 = 0;
+    while let Some(&c) = chars.peek() {
+        match c {
+            '#' | '♯' => accidental += 1,
+            'b' | '♭' => accidental -= 1,
+            'x' | '𝄪' => accidental += 2,
+            '𝄫' => accidental -= 2,
+            _ => break,
+        }
+        chars.next();
+    }
+    if accidental.abs() > 2 {
+        return Err(format!("Too many accidentals in '{}'", name));
+    }
+
+    let octave_text: String = chars.collect();
+    let octave = if octave_text.is_empty() {
+        middle_c_octave
+    } else {
+        octave_text
+            .parse::<i32>()
+            .map_err(|_| format!("Invalid octave in '{}'", name))?
+    };
+
+    // Checked so absurd octaves like "C2000000000" fail instead of overflowing
+    octave
+        .checked_sub(middle_c_octave)
+        .and_then(|o| o.checked_add(5))
+        .and_then(|o| o.checked_mul(12))
+        .and_then(|n| n.checked_add(pitch_class + accidental))
+        .and_then(|n| u8::try_from(n).ok())
+        .filter(|n| *n <= 127)
+        .ok_or_else(|| format!("Note '{}' is outside the MIDI range", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_port_prefers_exact_match() {
+        let ports = vec![
+            "USB MIDI 1".to_string(),
+            "USB MIDI".to_string(),
+            "IAC Driver Bus 1".to_string(),
+        ];
+        assert_eq!(find_port(&ports, "USB MIDI"), Some(1));
+        assert_eq!(find_port(&ports, "iac driver"), Some(2));
+        assert_eq!(find_port(&ports, "Missing"), None);
+    }
+
+    #[test]
+    fn test_find_port_ignores_alsa_client_id() {
+        let ports = vec!["Synth:Synth MIDI 1 28:0".to_string()];
+        assert_eq!(find_port(&ports, "Synth:Synth MIDI 1 24:0"), Some(0));
+        assert_eq!(find_port(&ports, "Synth:Synth MIDI 2 24:0"), None);
+    }
+
+    #[test]
+    fn test_parse_note_name() {
+        assert_eq!(parse_note_name("C4"), Ok(60));
+        assert_eq!(parse_note_name("F#2"), Ok(42));
+        assert_eq!(parse_note_name("Bb-1"), Ok(10));
+        assert_eq!(parse_note_name("c#5"), Ok(73));
+        assert_eq!(parse_note_name("Ebb3"), Ok(50));
+        assert_eq!(parse_note_name("Fx3"), Ok(55));
+        assert_eq!(parse_note_name("C-1"), Ok(0));
+        assert_eq!(parse_note_name("G9"), Ok(127));
+        assert!(parse_note_name("G#9").is_err());
+        assert!(parse_note_name("Cb-1").is_err());
+        assert!(parse_note_name("H2").is_err());
+        assert!(parse_note_name("C2000000000").is_err());
+        assert!(parse_note_name("C-2147483648").is_err());
+        assert!(parse_note_name_with_octave("C4", i32::MIN).is_err());
+    }
+
+    #[test]
+    fn test_parse_note_name_round_trips() {
+        for note in 0..=127 {
+            assert_eq!(parse_note_name(&midi_note_name(note)), Ok(note));
+        }
+    }
+
+    #[test]
+    fn test_parse_note_name_middle_c_octave() {
+        assert_eq!(parse_note_name_with_octave("C3", 3), Ok(60));
+        assert_eq!(parse_note_name_with_octave("A", 3), Ok(69));
+    }
+}
@@ -0,0 +1,219 @@
+//! Musical keys and scales - maps scale degrees to MIDI notes
+
+/// A set of semitone intervals above the root, within one octave.
+/// An empty `Custom` set plays as chromatic; build custom scales with `Scale::custom`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Scale {
+    Chromatic,
+    Major,
+    Minor,
+    Dorian,
+    Phrygian,
+    Lydian,
+    Mixolydian,
+    Locrian,
+    HarmonicMinor,
+    MajorPentatonic,
+    MinorPentatonic,
+    Custom(Vec<u8>),
+}
+
+impl Scale {
+    /// Built-in scales, in the order the GUI lists them
+    pub const PRESETS: [Scale; 11] = [
+        Scale::Chromatic,
+        Scale::Major,
+        Scale::Minor,
+        Scale::Dorian,
+        Scale::Phrygian,
+        Scale::Lydian,
+        Scale::Mixolydian,
+        Scale::Locrian,
+        Scale::HarmonicMinor,
+        Scale::MajorPentatonic,
+        Scale::MinorPentatonic,
+    ];
+
+    /// Build a user-defined scale. Intervals are folded into one octave,
+    /// sorted and deduplicated, and the root (0) is always included.
+    pub fn custom(intervals: &[u8]) -> Result<Self, String> {
+        if intervals.is_empty() {
+            return Err("A scale needs at least one interval".to_string());
+        }
+
+        let mut intervals: Vec<u8> = intervals.iter().map(|i| i % 12).collect();
+        intervals.push(0);
+        intervals.sort_unstable();
+        intervals.dedup();
+        Ok(Scale::Custom(intervals))
+    }
+
+    pub fn intervals(&self) -> &[u8] {
+        match self {
+            Scale::Chromatic => &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+            Scale::Major => &[0, 2, 4, 5, 7, 9, 11],
+            Scale::Minor => &[0, 2, 3, 5, 7, 8, 10],
+            Scale::Dorian => &[0, 2, 3, 5, 7, 9, 10],
+            Scale::Phrygian => &[0, 1, 3, 5, 7, 8, 10],
+            Scale::Lydian => &[0, 2, 4, 6, 7, 9, 11],
+            Scale::Mixolydian => &[0, 2, 4, 5, 7, 9, 10],
+            Scale::Locrian => &[0, 1, 3, 5, 6, 8, 10],
+            Scale::HarmonicMinor => &[0, 2, 3, 5, 7, 8, 11],
+            Scale::MajorPentatonic => &[0, 2, 4, 7, 9],
+            Scale::MinorPentatonic => &[0, 3, 5, 7, 10],
+            Scale::Custom(intervals) if intervals.is_empty() => Scale::Chromatic.intervals(),
+            Scale::Custom(intervals) => intervals,
+        }
+    }
+
+    /// Semitones above the root of a scale degree, wrapping into other octaves
+    pub fn semitones_for_degree(&self, degree: i32) -> i32 {
+        let intervals = self.intervals();
+        let len = intervals.len() as i32;
+        degree.div_euclid(len) * 12 + intervals[degree.rem_euclid(len) as usize] as i32
+    }
+
+    /// The degree nearest to a semitone offset from the root (ties round down)
+    pub fn degree_for_semitones(&self, semitones: i32) -> i32 {
+        self.degree_toward_semitones(semitones, -1)
+    }
+
+    /// The degree nearest to a semitone offset from the root, with ties
+    /// going up for a positive `direction` and down otherwise
+    pub fn degree_toward_semitones(&self, semitones: i32, direction: i32) -> i32 {
+        let len = self.intervals().len() as i32;
+        let octave = semitones.div_euclid(12);
+        (octave * len - len..=octave * len + len)
+            .min_by_key(|&degree| {
+                let distance = (self.semitones_for_degree(degree) - semitones).abs();
+                (distance, if direction > 0 { -degree } else { degree })
+            })
+            .unwrap_or(0)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scale::Chromatic => "Chromatic",
+            Scale::Major => "Major",
+            Scale::Minor => "Minor",
+            Scale::Dorian => "Dorian",
+            Scale::Phrygian => "Phrygian",
+            Scale::Lydian => "Lydian",
+            Scale::Mixolydian => "Mixolydian",
+            Scale::Locrian => "Locrian",
+            Scale::HarmonicMinor => "Harmonic Minor",
+            Scale::MajorPentatonic => "Major Pentatonic",
+            Scale::MinorPentatonic => "Minor Pentatonic",
+            Scale::Custom(_) => "Custom",
+        }
+    }
+}
+
+/// A root note plus a scale; steps store degrees and are resolved through this
+#[derive(Debug, Clone, PartialEq)]
+pub struct Key {
+    root: u8,
+    scale: Scale,
+}
+
+impl Key {
+    pub fn new(root: u8, scale: Scale) -> Self {
+        Self {
+            root: root.min(127),
+            scale,
+        }
+    }
+
+    pub fn root(&self) -> u8 {
+        self.root
+    }
+
+    pub fn set_root(&mut self, root: u8) {
+        self.root = root.min(127);
+    }
+
+    pub fn scale(&self) -> &Scale {
+        &self.scale
+    }
+
+    pub fn set_scale(&mut self, scale: Scale) {
+        self.scale = scale;
+    }
+
+    /// Resolve a scale degree (0 = root, negative = below) to a MIDI note.
+    /// Degrees past the end of the scale wrap into the next octave.
+    pub fn note_for_degree(&self, degree: i32) -> u8 {
+        (self.root as i32 + self.scale.semitones_for_degree(degree)).clamp(0, 127) as u8
+    }
+
+    /// Snap an arbitrary MIDI note to the nearest note in this key (ties round down)
+    pub fn quantize(&self, note: u8) -> u8 {
+        let offset = note as i32 - self.root as i32;
+        let octave = offset.div_euclid(12);
+        let pitch_class = offset.rem_euclid(12);
+
+        let nearest = self
+            .scale
+            .intervals()
+            .iter()
+            .flat_map(|&i| [i as i32 - 12, i as i32, i as i32 + 12])
+            .min_by_key(|&i| ((i - pitch_class).abs(), i))
+            .unwrap_or(0);
+
+        (self.root as i32 + octave * 12 + nearest).clamp(0, 127) as u8
+    }
+}
+
+impl Default for Key {
+    fn default() -> Self {
+        Self::new(60, Scale::Chromatic)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_note_for_degree_wraps_octaves() {
+        let key = Key::new(60, Scale::Major);
+        assert_eq!(key.note_for_degree(0), 60);
+        assert_eq!(key.note_for_degree(2), 64);
+        assert_eq!(key.note_for_degree(7), 72);
+        assert_eq!(key.note_for_degree(-1), 59);
+    }
+
+    #[test]
+    fn test_transposing_root_keeps_degrees_in_key() {
+        let mut key = Key::new(60, Scale::Minor);
+        let before: Vec<u8> = (0..7).map(|d| key.note_for_degree(d)).collect();
+        key.set_root(62);
+        let after: Vec<u8> = (0..7).map(|d| key.note_for_degree(d)).collect();
+        for (a, b) in before.iter().zip(&after) {
+            assert_eq!(b - a, 2);
+        }
+    }
+
+    #[test]
+    fn test_quantize() {
+        let key = Key::new(60, Scale::MajorPentatonic);
+        assert_eq!(key.quantize(61), 60);
+        assert_eq!(key.quantize(66), 67);
+        assert_eq!(key.quantize(71), 72);
+        assert_eq!(key.quantize(58), 57);
+    }
+
+    #[test]
+    fn test_custom_scale_normalizes() {
+        let scale = Scale::custom(&[7, 3, 15]).unwrap();
+        assert_eq!(scale.intervals(), &[0, 3, 7]);
+        assert!(Scale::custom(&[]).is_err());
+    }
+
+    #[test]
+    fn test_empty_custom_scale_is_chromatic() {
+        let key = Key::new(60, Scale::Custom(Vec::new()));
+        assert_eq!(key.note_for_degree(3), 63);
+        assert_eq!(Scale::Custom(Vec::new()).degree_for_semitones(-5), -5);
+    }
+}
@@ -0,0 +1,346 @@
+//! Standard MIDI File reading - just enough to pull note timing out of a file -
+//! and writing of rendered notes with their tempo map
+
+/// A note-on read from a MIDI file, timed in file ticks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SmfNote {
+    pub tick: u64,
+    pub note: u8,
+    pub velocity: u8,
+}
+
+/// A tempo meta event
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SmfTempo {
+    pub tick: u64,
+    pub bpm: f32,
+}
+
+/// A note to write, held for `length` ticks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SmfNoteSpan {
+    pub tick: u64,
+    pub length: u64,
+    pub note: u8,
+    pub velocity: u8,
+}
+
+/// Note-ons and tempo changes from every track of a file, sorted by time
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmfNotes {
+    pub ticks_per_quarter: u16,
+    pub notes: Vec<SmfNote>,
+    pub tempos: Vec<SmfTempo>,
+}
+
+pub fn read_notes(bytes: &[u8]) -> Result<SmfNotes, String> {
+    let mut reader = Reader { bytes, pos: 0 };
+
+    if reader.take(4)? != b"MThd" {
+        return Err("Not a MIDI file".to_string());
+    }
+    let header_len = reader.u32()? as usize;
+    let _format = reader.u16()?;
+    let track_count = reader.u16()?;
+    let division = reader.u16()?;
+    reader.take(header_len.saturating_sub(6))?;
+
+    if division & 0x8000 != 0 {
+        return Err("SMPTE-timed MIDI files are not supported".to_string());
+    }
+
+    let mut notes = Vec::new();
+    let mut tempos = Vec::new();
+    for _ in 0..track_count {
+        let chunk_type = reader.take(4)?;
+        let chunk_len = reader.u32()? as usize;
+        let chunk = reader.take(chunk_len)?;
+        if chunk_type == b"MTrk" {
+            read_track(chunk, &mut notes, &mut tempos)?;
+        }
+    }
+
+    notes.sort_by_key(|n| n.tick);
+    tempos.sort_by_key(|t| t.tick);
+    Ok(SmfNotes {
+        ticks_per_quarter: division,
+        notes,
+        tempos,
+    })
+}
+
+/// A format 0 file with the tempo map and notes on channel 1. At the same
+/// tick, tempo changes come first and note-offs precede note-ons, so a
+/// retriggered pitch is released before it starts again.
+pub fn write_smf(ticks_per_quarter: u16, tempos: &[SmfTempo], notes: &[SmfNoteSpan]) -> Vec<u8> {
+    // (tick, order at that tick, message)
+    let mut events: Vec<(u64, u8, Vec<u8>)> = Vec::new();
+    for tempo in tempos {
+        let micros_per_quarter = (60_000_000.0 / tempo.bpm as f64).round() as u32;
+        let mut message = vec![0xFF, 0x51, 0x03];
+        message.extend(&micros_per_quarter.min(0xFF_FFFF).to_be_bytes()[1..]);
+        events.push((tempo.tick, 0, message));
+    }
+    for note in notes {
+        let velocity = note.velocity.clamp(1, 127);
+        let end = note.tick + note.length.max(1);
+        events.push((note.tick, 2, vec![0x90, note.note & 0x7F, velocity]));
+        events.push((end, 1, vec![0x80, note.note & 0x7F, 0]));
+    }
+    events.sort_by_key(|&(tick, order, _)| (tick, order));
+
+    let mut track = Vec::new();
+    let mut last_tick = 0;
+    for (tick, _, message) in events {
+        write_vlq(&mut track, (tick - last_tick).min(0x0FFF_FFFF) as u32);
+        last_tick = tick;
+        track.extend(message);
+    }
+    track.extend([0x00, 0xFF, 0x2F, 0x00]); // end of track
+    format0_file(ticks_per_quarter, &track)
+}
+
+/// Wrap one track's events in the header and chunk of a format 0 file
+pub(crate) fn format0_file(ticks_per_quarter: u16, track: &[u8]) -> Vec<u8> {
+    let mut bytes = b"MThd".to_vec();
+    bytes.extend(6u32.to_be_bytes());
+    bytes.extend(0u16.to_be_bytes()); // format 0
+    bytes.extend(1u16.to_be_bytes()); // one track
+    bytes.extend(ticks_per_quarter.to_be_bytes());
+    bytes.extend(b"MTrk");
+    bytes.extend((track.len() as u32).to_be_bytes());
+    bytes.extend(track);
+    bytes
+}
+
+fn write_vlq(bytes: &mut Vec<u8>, value: u32) {
+    let mut groups = vec![(value & 0x7F) as u8];
+    let mut rest = value >> 7;
+    while rest > 0 {
+        groups.push((rest & 0x7F) as u8 | 0x80);
+        rest >>= 7;
+    }
+    bytes.extend(groups.iter().rev());
+}
+
+fn read_track(
+    bytes: &[u8],
+    notes: &mut Vec<SmfNote>,
+    tempos: &mut Vec<SmfTempo>,
+) -> Result<(), String> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let mut tick = 0u64;
+    let mut running_status = None;
+
+    while !reader.is_done() {
+        tick += reader.vlq()? as u64;
+
+        let mut status = reader.u8()?;
+        let first_data = if status < 0x80 {
+            // Running status: this byte is already the first data byte
+            let data = status;
+            status = running_status.ok_or("Data byte without a status")?;
+            Some(data)
+        } else {
+            None
+        };
+
+        match status {
+            0xFF => {
+                let meta_type = reader.u8()?;
+                let len = reader.vlq()? as usize;
+                let data = reader.take(len)?;
+                if meta_type == 0x51 && len == 3 {
+                    let micros = u32::from_be_bytes([0, data[0], data[1], data[2]]);
+                    if micros > 0 {
+                        tempos.push(SmfTempo {
+                            tick,
+                            bpm: (60_000_000.0 / micros as f64) as f32,
+                        });
+                    }
+                }
+            }
+            0xF0 | 0xF7 => {
+                let len = reader.vlq()? as usize;
+                reader.take(len)?;
+            }
+            0x80..=0xEF => {
+                running_status = Some(status);
+                let data1 = match first_data {
+                    Some(data) => data,
+                    None => reader.u8()?,
+                };
+                let has_two_data_bytes = !matches!(status & 0xF0, 0xC0 | 0xD0);
+                let data2 = if has_two_data_bytes { reader.u8()? } else { 0 };
+
+                if status & 0xF0 == 0x90 && data2 > 0 {
+                    notes.push(SmfNote {
+                        tick,
+                        note: data1,
+                        velocity: data2,
+                    });
+                }
+            }
+            _ => return Err(format!("Unsupported MIDI status byte {:#04x}", status)),
+        }
+    }
+
+    Ok(())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn is_done(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self.pos + len;
+        let slice = self
+            .bytes
+            .get(self.pos..end)
+            .ok_or("Unexpected end of MIDI file")?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let b = self.take(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.take(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// Variable-length quantity, at most four bytes
+    fn vlq(&mut self) -> Result<u32, String> {
+        let mut value = 0u32;
+        for _ in 0..4 {
+            let byte = self.u8()?;
+            value = (value << 7) | (byte & 0x7F) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("Invalid variable-length quantity".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_notes_with_running_status() {
+        let track: Vec<u8> = vec![
+            0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20, // tempo meta
+            0x00, 0x90, 0x3C, 0x64, // note on C4
+            0x60, 0x3C, 0x00, // running status note off (velocity 0)
+            0x10, 0x3E, 0x50, // running status note on D4
+            0x00, 0xFF, 0x2F, 0x00, // end of track
+        ];
+        let bytes = format0_file(96, &track);
+
+        let smf = read_notes(&bytes).unwrap();
+        assert_eq!(smf.ticks_per_quarter, 96);
+        assert_eq!(
+            smf.tempos,
+            vec![SmfTempo {
+                tick: 0,
+                bpm: 120.0
+            }]
+        );
+        assert_eq!(
+            smf.notes,
+            vec![
+                SmfNote {
+                    tick: 0,
+                    note: 60,
+                    velocity: 100
+                },
+                SmfNote {
+                    tick: 112,
+                    note: 62,
+                    velocity: 80
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_written_file_round_trips() {
+        let tempos = vec![
+            SmfTempo {
+                tick: 0,
+                bpm: 120.0,
+            },
+            SmfTempo {
+                tick: 200,
+                bpm: 150.0,
+            },
+        ];
+        let notes = vec![
+            SmfNoteSpan {
+                tick: 0,
+                length: 48,
+                note: 60,
+                velocity: 100,
+            },
+            SmfNoteSpan {
+                tick: 48,
+                length: 200,
+                note: 60,
+                velocity: 90,
+            },
+            SmfNoteSpan {
+                tick: 300,
+                length: 20,
+                note: 67,
+                velocity: 80,
+            },
+        ];
+        let bytes = write_smf(96, &tempos, &notes);
+        let smf = read_notes(&bytes).unwrap();
+        assert_eq!(smf.ticks_per_quarter, 96);
+        assert_eq!(smf.tempos, tempos);
+        assert_eq!(
+            smf.notes,
+            vec![
+                SmfNote {
+                    tick: 0,
+                    note: 60,
+                    velocity: 100
+                },
+                SmfNote {
+                    tick: 48,
+                    note: 60,
+                    velocity: 90
+                },
+                SmfNote {
+                    tick: 300,
+                    note: 67,
+                    velocity: 80
+                },
+            ]
+        );
+
+        // The first C is released before it is struck again at tick 48
+        let retrigger = [0x80, 0x3C, 0x00, 0x00, 0x90, 0x3C, 0x5A];
+        assert!(bytes.windows(retrigger.len()).any(|w| w == retrigger));
+    }
+
+    #[test]
+    fn test_rejects_non_midi() {
+        assert!(read_notes(b"RIFF....").is_err());
+    }
+}
@@ -0,0 +1,108 @@
+/// Output sinks - pluggable destinations for playback events
+use crate::sequencer::playback::PlaybackEvent;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+/// Anything that can consume playback events (synth, MIDI port, OSC, file...)
+pub trait EventSink {
+    fn handle_event(&mut self, event: &PlaybackEvent) -> Result<(), String>;
+}
+
+/// Shared sinks can be registered while the caller keeps a handle to them
+impl<S: EventSink> EventSink for Arc<Mutex<S>> {
+    fn handle_event(&mut self, event: &PlaybackEvent) -> Result<(), String> {
+        self.lock()
+            .map_err(|_| "Sink lock poisoned".to_string())?
+            .handle_event(event)
+    }
+}
+
+/// Sinks that can't leave their thread (like the audio stream) are shared this way
+impl<S: EventSink> EventSink for Rc<RefCell<S>> {
+    fn handle_event(&mut self, event: &PlaybackEvent) -> Result<(), String> {
+        self.borrow_mut().handle_event(event)
+    }
+}
+
+/// Fans each playback event out to every registered sink
+pub struct EventDispatcher {
+    sinks: Vec<Box<dyn EventSink>>,
+}
+
+impl EventDispatcher {
+    pub fn new() -> Self {
+        Self { sinks: Vec::new() }
+    }
+
+    pub fn add_sink<S: EventSink + 'static>(&mut self, sink: S) {
+        self.sinks.push(Box::new(sink));
+    }
+
+    pub fn sink_count(&self) -> usize {
+        self.sinks.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.sinks.clear();
+    }
+
+    /// Deliver an event to all sinks, collecting any errors along the way
+    pub fn dispatch(&mut self, event: &PlaybackEvent) -> Vec<String> {
+        self.sinks
+            .iter_mut()
+            .filter_map(|sink| sink.handle_event(event).err())
+            .collect()
+    }
+
+    pub fn dispatch_all(&mut self, events: &[PlaybackEvent]) -> Vec<String> {
+        events
+            .iter()
+            .flat_map(|event| self.dispatch(event))
+            .collect()
+    }
+}
+
+impl Default for EventDispatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Recorder(Vec<String>);
+
+    impl EventSink for Recorder {
+        fn handle_event(&mut self, event: &PlaybackEvent) -> Result<(), String> {
+            self.0.push(format!("{:?}", event));
+            Ok(())
+        }
+    }
+
+    struct Failing;
+
+    impl EventSink for Failing {
+        fn handle_event(&mut self, _event: &PlaybackEvent) -> Result<(), String> {
+            Err("nope".to_string())
+        }
+    }
+
+    #[test]
+    fn test_dispatch_fans_out() {
+        let first = Arc::new(Mutex::new(Recorder(Vec::new())));
+        let second = Arc::new(Mutex::new(Recorder(Vec::new())));
+
+        let mut dispatcher = EventDispatcher::new();
+        dispatcher.add_sink(Arc::clone(&first));
+        dispatcher.add_sink(Failing);
+        dispatcher.add_sink(Arc::clone(&second));
+
+        let errors = dispatcher.dispatch(&PlaybackEvent::NoteOn(60, 100));
+        assert_eq!(errors, vec!["nope".to_string()]);
+        assert_eq!(first.lock().unwrap().0.len(), 1);
+        assert_eq!(second.lock().unwrap().0.len(), 1);
+    }
+}
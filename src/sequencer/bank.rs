@@ -0,0 +1,133 @@
+//! Pattern banks - multiple grids with queued switching at loop boundaries
+use super::Grid;
+
+#[derive(Debug, Clone)]
+pub struct PatternBank {
+    patterns: Vec<Grid>,
+    current: usize,
+    queued: Option<usize>,
+}
+
+impl PatternBank {
+    pub const DEFAULT_SLOTS: usize = 16;
+
+    pub fn new(slots: usize, width: usize, height: usize) -> Self {
+        Self {
+            patterns: vec![Grid::new(width, height); slots.max(1)],
+            current: 0,
+            queued: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    pub fn current_index(&self) -> usize {
+        self.current
+    }
+
+    pub fn current(&self) -> &Grid {
+        &self.patterns[self.current]
+    }
+
+    pub fn current_mut(&mut self) -> &mut Grid {
+        &mut self.patterns[self.current]
+    }
+
+    pub fn pattern(&self, index: usize) -> Option<&Grid> {
+        self.patterns.get(index)
+    }
+
+    pub fn pattern_mut(&mut self, index: usize) -> Option<&mut Grid> {
+        self.patterns.get_mut(index)
+    }
+
+    /// Switch immediately, dropping any queued change
+    pub fn select(&mut self, index: usize) {
+        if index < self.patterns.len() {
+            self.current = index;
+            self.queued = None;
+        }
+    }
+
+    /// Queue a pattern to take over when the current loop ends
+    pub fn queue(&mut self, index: usize) {
+        if index < self.patterns.len() {
+            self.queued = Some(index);
+        }
+    }
+
+    pub fn queued(&self) -> Option<usize> {
+        self.queued
+    }
+
+    pub fn cancel_queue(&mut self) {
+        self.queued = None;
+    }
+
+    /// Called at the end of a loop; returns the new index if a switch happened
+    pub fn apply_queued(&mut self) -> Option<usize> {
+        let next = self.queued.take()?;
+        self.current = next;
+        Some(next)
+    }
+
+    pub fn copy_pattern(&mut self, from: usize, to: usize) {
+        if let Some(source) = self.patterns.get(from).cloned() {
+            if let Some(target) = self.patterns.get_mut(to) {
+                *target = source;
+            }
+        }
+    }
+
+    pub fn clear_pattern(&mut self, index: usize) {
+        if let Some(pattern) = self.patterns.get_mut(index) {
+            pattern.clear();
+        }
+    }
+
+    /// Copy pattern contents from another bank, keeping this bank's
+    /// current and queued selection untouched
+    pub fn sync_patterns_from(&mut self, other: &PatternBank) {
+        self.patterns.clone_from(&other.patterns);
+    }
+}
+
+impl Default for PatternBank {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_SLOTS, 8, 8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queued_switch_waits_for_loop_end() {
+        let mut bank = PatternBank::new(4, 4, 4);
+        bank.queue(2);
+        assert_eq!(bank.current_index(), 0);
+        assert_eq!(bank.queued(), Some(2));
+
+        assert_eq!(bank.apply_queued(), Some(2));
+        assert_eq!(bank.current_index(), 2);
+        assert_eq!(bank.apply_queued(), None);
+    }
+
+    #[test]
+    fn test_copy_and_clear_pattern() {
+        let mut bank = PatternBank::new(2, 4, 4);
+        bank.clear_pattern(0);
+        assert!(!bank.current().get(0, 0));
+        assert!(bank.pattern(1).unwrap().get(0, 0));
+
+        bank.copy_pattern(0, 1);
+        assert!(!bank.pattern(1).unwrap().get(0, 0));
+    }
+}
@@ -0,0 +1,419 @@
+//! Copy/paste of grid regions, with a plain-text clipboard format
+//!
+//! A clip is written as a header line and one line per row, e.g.
+//!
+//! ```text
+//! sqnc-clip 1 3x2
+//! x . x[d=2,p=50]
+//! . x[c=1:4,r=3,v=up,g=1.5,t,s,n=-12] .
+//! ```
+//!
+//! `x` is an active step and `.` an inactive one; attributes that differ
+//! from a default step follow in brackets.
+use super::{Condition, Grid, Step, VelocityRamp};
+
+const HEADER: &str = "sqnc-clip";
+const VERSION: u32 = 1;
+
+/// A rectangle of cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region {
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// The rectangle spanned by two corner cells, in either order
+    pub fn from_corners(a: (usize, usize), b: (usize, usize)) -> Self {
+        Self {
+            x: a.0.min(b.0),
+            y: a.1.min(b.1),
+            width: a.0.abs_diff(b.0) + 1,
+            height: a.1.abs_diff(b.1) + 1,
+        }
+    }
+
+    /// Every cell of a grid
+    pub fn whole(grid: &Grid) -> Self {
+        Self::new(0, 0, grid.width(), grid.height())
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        (self.x..self.x + self.width).contains(&x) && (self.y..self.y + self.height).contains(&y)
+    }
+
+    pub fn cell_count(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// This region cut down to fit inside a grid
+    pub fn clipped_to(&self, grid: &Grid) -> Self {
+        let x = self.x.min(grid.width());
+        let y = self.y.min(grid.height());
+        Self {
+            x,
+            y,
+            width: self.width.min(grid.width() - x),
+            height: self.height.min(grid.height() - y),
+        }
+    }
+}
+
+/// How a clip fills a paste target of a different size
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PasteMode {
+    /// Paste once, dropping whatever doesn't fit
+    #[default]
+    Clip,
+    /// Repeat the clip until the target is full
+    Tile,
+}
+
+impl PasteMode {
+    pub const ALL: [PasteMode; 2] = [PasteMode::Clip, PasteMode::Tile];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PasteMode::Clip => "Clip",
+            PasteMode::Tile => "Tile",
+        }
+    }
+}
+
+/// Copied steps, row by row
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clip {
+    width: usize,
+    height: usize,
+    steps: Vec<Step>,
+}
+
+impl Clip {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn step(&self, x: usize, y: usize) -> Option<&Step> {
+        if x < self.width {
+            self.steps.get(y * self.width + x)
+        } else {
+            None
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{} {} {}x{}\n", HEADER, VERSION, self.width, self.height);
+        for row in self.steps.chunks(self.width.max(1)) {
+            let tokens: Vec<String> = row.iter().map(step_to_text).collect();
+            text.push_str(&tokens.join(" "));
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Parse a clip out of text, ignoring anything before the header line
+    /// (such as a chat message it was pasted into)
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let mut lines = text
+            .lines()
+            .map(str::trim)
+            .skip_while(|line| !line.starts_with(HEADER));
+
+        let header = lines.next().ok_or("No sqnc clip found in the text")?;
+        let fields: Vec<&str> = header.split_whitespace().collect();
+        let (version, size) = match fields.as_slice() {
+            [_, version, size] => (*version, *size),
+            _ => return Err(format!("Invalid clip header '{}'", header)),
+        };
+        if version.parse::<u32>().ok() != Some(VERSION) {
+            return Err(format!("Unsupported clip version '{}'", version));
+        }
+        let (width, height) = size
+            .split_once('x')
+            .and_then(|(w, h)| Some((w.parse::<usize>().ok()?, h.parse::<usize>().ok()?)))
+            .filter(|&(w, h)| w > 0 && h > 0)
+            .ok_or_else(|| format!("Invalid clip size '{}'", size))?;
+        // Checked before allocating, as the text may come from anywhere
+        if width > Grid::MAX_WIDTH || height > Grid::MAX_HEIGHT {
+            return Err(format!(
+                "Clip size {}x{} is larger than the {}x{} maximum",
+                width,
+                height,
+                Grid::MAX_WIDTH,
+                Grid::MAX_HEIGHT
+            ));
+        }
+
+        let mut steps = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let line = lines
+                .next()
+                .ok_or_else(|| format!("Clip is missing row {}", y + 1))?;
+            let row = line
+                .split_whitespace()
+                .map(step_from_text)
+                .collect::<Result<Vec<_>, _>>()?;
+            if row.len() != width {
+                return Err(format!(
+                    "Row {} has {} steps, expected {}",
+                    y + 1,
+                    row.len(),
+                    width
+                ));
+            }
+            steps.extend(row);
+        }
+
+        Ok(Self {
+            width,
+            height,
+            steps,
+        })
+    }
+}
+
+impl Grid {
+    pub fn copy_region(&self, region: &Region) -> Clip {
+        let region = region.clipped_to(self);
+        let steps = (region.y..region.y + region.height)
+            .flat_map(|y| (region.x..region.x + region.width).map(move |x| (x, y)))
+            .map(|(x, y)| self.step(x, y))
+            .collect();
+        Clip {
+            width: region.width,
+            height: region.height,
+            steps,
+        }
+    }
+
+    /// Copy a region, then reset its steps to empty defaults
+    pub fn cut_region(&mut self, region: &Region) -> Clip {
+        let clip = self.copy_region(region);
+        for y in region.y..region.y + region.height {
+            for x in region.x..region.x + region.width {
+                if let Some(step) = self.step_mut(x, y) {
+                    *step = Step::default();
+                }
+            }
+        }
+        clip
+    }
+
+    /// Paste a clip into `target`, clipping or tiling when the sizes differ.
+    /// Cells outside the grid are skipped.
+    pub fn paste(&mut self, clip: &Clip, target: &Region, mode: PasteMode) {
+        if clip.width == 0 || clip.height == 0 {
+            return;
+        }
+        let (width, height) = match mode {
+            PasteMode::Clip => (clip.width.min(target.width), clip.height.min(target.height)),
+            PasteMode::Tile => (target.width, target.height),
+        };
+
+        for dy in 0..height {
+            for dx in 0..width {
+                let source = clip.step(dx % clip.width, dy % clip.height).copied();
+                if let (Some(step), Some(cell)) =
+                    (source, self.step_mut(target.x + dx, target.y + dy))
+                {
+                    *cell = step;
+                }
+            }
+        }
+    }
+}
+
+fn step_to_text(step: &Step) -> String {
+    let default = Step::default();
+    let mut attributes = Vec::new();
+    if step.degree != default.degree {
+        attributes.push(format!("d={}", step.degree));
+    }
+    if step.probability != default.probability {
+        attributes.push(format!("p={}", step.probability));
+    }
+    if step.condition != default.condition {
+        attributes.push(format!("c={}", step.condition.name()));
+    }
+    if step.ratchet != default.ratchet {
+        attributes.push(format!("r={}", step.ratchet));
+    }
+    match step.velocity_ramp {
+        VelocityRamp::Flat => {}
+        VelocityRamp::Up => attributes.push("v=up".to_string()),
+        VelocityRamp::Down => attributes.push("v=down".to_string()),
+    }
+    if step.gate != default.gate {
+        attributes.push(format!("g={}", step.gate));
+    }
+    if step.tie {
+        attributes.push("t".to_string());
+    }
+    if step.slide {
+        attributes.push("s".to_string());
+    }
+    if step.nudge != default.nudge {
+        attributes.push(format!("n={}", step.nudge));
+    }
+
+    let state = if step.active { "x" } else { "." };
+    if attributes.is_empty() {
+        state.to_string()
+    } else {
+        format!("{}[{}]", state, attributes.join(","))
+    }
+}
+
+fn step_from_text(token: &str) -> Result<Step, String> {
+    let (state, attributes) = match token.split_once('[') {
+        Some((state, rest)) => {
+            let attributes = rest
+                .strip_suffix(']')
+                .ok_or_else(|| format!("Unclosed attributes in '{}'", token))?;
+            (state, attributes)
+        }
+        None => (token, ""),
+    };
+
+    let mut step = Step::new(match state {
+        "x" => true,
+        "." => false,
+        _ => return Err(format!("Invalid step '{}'", token)),
+    });
+
+    for attribute in attributes.split(',').filter(|a| !a.is_empty()) {
+        let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+        let invalid = || format!("Invalid step attribute '{}'", attribute);
+        match key {
+            "d" => step.degree = value.parse().map_err(|_| invalid())?,
+            "p" => step.probability = value.parse::<u8>().map_err(|_| invalid())?.min(100),
+            "c" => step.condition = parse_condition(value).ok_or_else(invalid)?,
+            "r" => step.ratchet = value.parse::<u8>().map_err(|_| invalid())?.clamp(1, 8),
+            "v" => {
+                step.velocity_ramp = match value {
+                    "flat" => VelocityRamp::Flat,
+                    "up" => VelocityRamp::Up,
+                    "down" => VelocityRamp::Down,
+                    _ => return Err(invalid()),
+                }
+            }
+            "g" => {
+                let gate: f32 = value
+                    .parse()
+                    .ok()
+                    .filter(|gate: &f32| gate.is_finite())
+                    .ok_or_else(invalid)?;
+                step.gate = gate.clamp(Step::MIN_GATE, Step::MAX_GATE);
+            }
+            "t" => step.tie = true,
+            "s" => step.slide = true,
+            "n" => {
+                let nudge: i8 = value.parse().map_err(|_| invalid())?;
+                step.nudge = nudge.clamp(-Step::MAX_NUDGE, Step::MAX_NUDGE);
+            }
+            _ => return Err(invalid()),
+        }
+    }
+
+    Ok(step)
+}
+
+fn parse_condition(name: &str) -> Option<Condition> {
+    if let Some((a, b)) = name.split_once(':') {
+        let (a, b) = (a.parse::<u8>().ok()?, b.parse::<u8>().ok()?);
+        return (a >= 1 && a <= b).then_some(Condition::Ratio(a, b));
+    }
+    Condition::ALL
+        .into_iter()
+        .find(|condition| condition.name() == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clip_text_round_trips() {
+        let mut grid = Grid::new(3, 2);
+        grid.set(1, 0, false);
+        let step = grid.step_mut(2, 1).unwrap();
+        *step = Step {
+            degree: -3,
+            probability: 50,
+            condition: Condition::Ratio(1, 4),
+            ratchet: 3,
+            velocity_ramp: VelocityRamp::Up,
+            gate: 1.5,
+            tie: true,
+            slide: true,
+            nudge: -12,
+            ..Step::new(true)
+        };
+
+        let clip = grid.copy_region(&Region::whole(&grid));
+        let text = clip.to_text();
+        assert_eq!(
+            text,
+            "sqnc-clip 1 3x2\nx . x\nx x x[d=-3,p=50,c=1:4,r=3,v=up,g=1.5,t,s,n=-12]\n"
+        );
+
+        let pasted_in_chat = format!("here's the hat pattern:\n\n{}\nenjoy", text);
+        assert_eq!(Clip::from_text(&pasted_in_chat), Ok(clip));
+    }
+
+    #[test]
+    fn test_clip_text_errors() {
+        assert!(Clip::from_text("hello").is_err());
+        assert!(Clip::from_text("sqnc-clip 1 2x1\nx").is_err());
+        assert!(Clip::from_text("sqnc-clip 1 1x1\nx[q=1]").is_err());
+        assert!(Clip::from_text("sqnc-clip 9 1x1\nx").is_err());
+        assert!(Clip::from_text("sqnc-clip 1 1x1\nx[g=nan]").is_err());
+        assert!(Clip::from_text("sqnc-clip 1 1x1\nx[g=inf]").is_err());
+        assert!(Clip::from_text("sqnc-clip 1 99999999999x99999999999\nx").is_err());
+        assert!(Clip::from_text("sqnc-clip 1 100000x100000\nx").is_err());
+    }
+
+    #[test]
+    fn test_paste_clips_or_tiles() {
+        let mut source = Grid::new(2, 1);
+        source.set(1, 0, false);
+        let clip = source.copy_region(&Region::whole(&source));
+
+        let mut grid = Grid::new(5, 1);
+        grid.clear();
+        grid.paste(&clip, &Region::new(2, 0, 3, 1), PasteMode::Clip);
+        let row: Vec<bool> = (0..5).map(|x| grid.get(x, 0)).collect();
+        assert_eq!(row, vec![false, false, true, false, false]);
+
+        grid.clear();
+        grid.paste(&clip, &Region::new(0, 0, 5, 1), PasteMode::Tile);
+        let row: Vec<bool> = (0..5).map(|x| grid.get(x, 0)).collect();
+        assert_eq!(row, vec![true, false, true, false, true]);
+    }
+
+    #[test]
+    fn test_cut_and_region_clipping() {
+        let mut grid = Grid::new(4, 4);
+        let clip = grid.cut_region(&Region::from_corners((3, 3), (2, 2)));
+        assert_eq!((clip.width(), clip.height()), (2, 2));
+        assert!(!grid.get(3, 3));
+
+        let clip = grid.copy_region(&Region::new(3, 0, 5, 1));
+        assert_eq!(clip.width(), 1);
+    }
+}
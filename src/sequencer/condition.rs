@@ -0,0 +1,140 @@
+//! Conditional triggers - Elektron-style rules deciding whether a step fires
+use super::Step;
+use fastrand::Rng;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Condition {
+    #[default]
+    Always,
+    /// "A:B" - fire on the A-th loop out of every B loops
+    Ratio(u8, u8),
+    Fill,
+    NotFill,
+    /// Only on the first loop after the pattern starts
+    First,
+    NotFirst,
+    /// Only if the previously played step fired
+    Previous,
+    NotPrevious,
+}
+
+impl Condition {
+    /// Condition kinds for pickers; `Ratio` is listed as 1:2
+    pub const ALL: [Condition; 8] = [
+        Condition::Always,
+        Condition::Ratio(1, 2),
+        Condition::Fill,
+        Condition::NotFill,
+        Condition::First,
+        Condition::NotFirst,
+        Condition::Previous,
+        Condition::NotPrevious,
+    ];
+
+    pub fn name(&self) -> String {
+        match self {
+            Condition::Always => "Always".to_string(),
+            Condition::Ratio(a, b) => format!("{}:{}", a, b),
+            Condition::Fill => "Fill".to_string(),
+            Condition::NotFill => "!Fill".to_string(),
+            Condition::First => "1st".to_string(),
+            Condition::NotFirst => "!1st".to_string(),
+            Condition::Previous => "Pre".to_string(),
+            Condition::NotPrevious => "!Pre".to_string(),
+        }
+    }
+
+    pub fn is_met(&self, context: &TriggerContext) -> bool {
+        match *self {
+            Condition::Always => true,
+            Condition::Ratio(a, b) => {
+                let b = b.max(1) as usize;
+                let a = (a.max(1) as usize).min(b);
+                context.loop_count % b == a - 1
+            }
+            Condition::Fill => context.fill,
+            Condition::NotFill => !context.fill,
+            Condition::First => context.loop_count == 0,
+            Condition::NotFirst => context.loop_count != 0,
+            Condition::Previous => context.previous_fired,
+            Condition::NotPrevious => !context.previous_fired,
+        }
+    }
+}
+
+/// Playback state that conditions are evaluated against
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TriggerContext {
+    /// Completed loops of the current pattern, starting at 0
+    pub loop_count: usize,
+    pub fill: bool,
+    pub previous_fired: bool,
+}
+
+impl Step {
+    /// Decide whether an active step fires: its condition must hold, then
+    /// its probability is rolled. Pass a seeded `Rng` for reproducible renders.
+    pub fn fires(&self, context: &TriggerContext, rng: &mut Rng) -> bool {
+        if !self.active || !self.condition.is_met(context) {
+            return false;
+        }
+        self.probability >= 100 || rng.u8(0..100) < self.probability
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(loop_count: usize) -> TriggerContext {
+        TriggerContext {
+            loop_count,
+            ..TriggerContext::default()
+        }
+    }
+
+    #[test]
+    fn test_ratio_condition() {
+        let condition = Condition::Ratio(2, 3);
+        let fired: Vec<bool> = (0..6).map(|n| condition.is_met(&context(n))).collect();
+        assert_eq!(fired, vec![false, true, false, false, true, false]);
+    }
+
+    #[test]
+    fn test_probability_is_reproducible_with_seed() {
+        let step = Step {
+            probability: 50,
+            ..Step::new(true)
+        };
+        let roll = |seed| {
+            let mut rng = Rng::with_seed(seed);
+            (0..32)
+                .map(|_| step.fires(&context(0), &mut rng))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(roll(42), roll(42));
+        assert!(roll(42).contains(&true) && roll(42).contains(&false));
+    }
+
+    #[test]
+    fn test_fill_and_previous_conditions() {
+        let step = Step {
+            condition: Condition::Fill,
+            ..Step::new(true)
+        };
+        let mut rng = Rng::with_seed(1);
+        assert!(!step.fires(&context(0), &mut rng));
+        let fill = TriggerContext {
+            fill: true,
+            ..context(0)
+        };
+        assert!(step.fires(&fill, &mut rng));
+
+        let previous = TriggerContext {
+            previous_fired: true,
+            ..context(3)
+        };
+        assert!(Condition::Previous.is_met(&previous));
+        assert!(!Condition::NotPrevious.is_met(&previous));
+    }
+}
@@ -0,0 +1,161 @@
+//! Playback direction modes and the playhead that walks a pattern
+use fastrand::Rng;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Direction {
+    #[default]
+    Forward,
+    Reverse,
+    /// Bounce between the ends without repeating them: 0 1 2 3 2 1 0 1 ...
+    PingPong,
+    /// Bounce and play each end twice: 0 1 2 3 3 2 1 0 0 1 ...
+    PingPongRepeat,
+    Random,
+    /// Brownian motion: move one step left or right at random, wrapping around
+    RandomWalk,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 6] = [
+        Direction::Forward,
+        Direction::Reverse,
+        Direction::PingPong,
+        Direction::PingPongRepeat,
+        Direction::Random,
+        Direction::RandomWalk,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Direction::Forward => "Forward",
+            Direction::Reverse => "Reverse",
+            Direction::PingPong => "Ping-pong",
+            Direction::PingPongRepeat => "Ping-pong (repeat ends)",
+            Direction::Random => "Random",
+            Direction::RandomWalk => "Random walk",
+        }
+    }
+}
+
+/// Tracks the current step and, for ping-pong, which way it is heading
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Playhead {
+    position: usize,
+    backward: bool,
+}
+
+impl Playhead {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: usize) {
+        self.position = position;
+    }
+
+    /// Put the playhead on the first step a direction plays
+    pub fn restart(&mut self, direction: Direction, length: usize) {
+        self.backward = direction == Direction::Reverse;
+        self.position = if self.backward {
+            length.saturating_sub(1)
+        } else {
+            0
+        };
+    }
+
+    /// Move to the next step of a pattern with `length` steps
+    pub fn advance(&mut self, direction: Direction, length: usize, rng: &mut Rng) -> usize {
+        if length <= 1 {
+            self.position = 0;
+            return self.position;
+        }
+
+        let last = length - 1;
+        let position = self.position.min(last);
+        self.position = match direction {
+            Direction::Forward => (position + 1) % length,
+            Direction::Reverse => (position + last) % length,
+            Direction::PingPong => {
+                if (self.backward && position == 0) || (!self.backward && position == last) {
+                    self.backward = !self.backward;
+                }
+                if self.backward {
+                    position - 1
+                } else {
+                    position + 1
+                }
+            }
+            Direction::PingPongRepeat => {
+                let at_end = if self.backward {
+                    position == 0
+                } else {
+                    position == last
+                };
+                if at_end {
+                    self.backward = !self.backward;
+                    position
+                } else if self.backward {
+                    position - 1
+                } else {
+                    position + 1
+                }
+            }
+            Direction::Random => rng.usize(0..length),
+            Direction::RandomWalk => {
+                if rng.bool() {
+                    (position + 1) % length
+                } else {
+                    (position + last) % length
+                }
+            }
+        };
+        self.position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn walk(direction: Direction, length: usize, steps: usize) -> Vec<usize> {
+        let mut rng = Rng::with_seed(7);
+        let mut playhead = Playhead::new();
+        playhead.restart(direction, length);
+        let mut positions = vec![playhead.position()];
+        for _ in 1..steps {
+            positions.push(playhead.advance(direction, length, &mut rng));
+        }
+        positions
+    }
+
+    #[test]
+    fn test_linear_directions() {
+        assert_eq!(walk(Direction::Forward, 3, 5), vec![0, 1, 2, 0, 1]);
+        assert_eq!(walk(Direction::Reverse, 3, 5), vec![2, 1, 0, 2, 1]);
+    }
+
+    #[test]
+    fn test_ping_pong() {
+        assert_eq!(
+            walk(Direction::PingPong, 4, 8),
+            vec![0, 1, 2, 3, 2, 1, 0, 1]
+        );
+        assert_eq!(
+            walk(Direction::PingPongRepeat, 3, 9),
+            vec![0, 1, 2, 2, 1, 0, 0, 1, 2]
+        );
+    }
+
+    #[test]
+    fn test_random_walk_moves_one_step() {
+        let positions = walk(Direction::RandomWalk, 8, 64);
+        for pair in positions.windows(2) {
+            let distance = (pair[0] as i32 - pair[1] as i32).rem_euclid(8);
+            assert!(distance == 1 || distance == 7);
+        }
+    }
+}
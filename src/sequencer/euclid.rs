@@ -0,0 +1,82 @@
+/// Euclidean rhythm generation - spreads k hits as evenly as possible over n steps
+use super::Grid;
+
+/// Build a Euclidean pattern of `hits` over `steps`, rotated right by `rotation`.
+/// Uses Bjorklund's algorithm, so the unrotated pattern matches the canonical
+/// form found in other sequencers, e.g. E(3, 8) = x..x..x. and E(5, 8) = x.xx.xx.
+pub fn euclidean(hits: usize, steps: usize, rotation: usize) -> Vec<bool> {
+    if steps == 0 {
+        return Vec::new();
+    }
+
+    let mut pattern = bjorklund(hits.min(steps), steps);
+    pattern.rotate_right(rotation % steps);
+    pattern
+}
+
+fn bjorklund(hits: usize, steps: usize) -> Vec<bool> {
+    if hits == 0 {
+        return vec![false; steps];
+    }
+
+    let mut groups = vec![vec![true]; hits];
+    let mut remainder = vec![vec![false]; steps - hits];
+
+    // Repeatedly pair groups with remainders until at most one remainder is left
+    while remainder.len() > 1 {
+        let paired = groups.len().min(remainder.len());
+        let leftover = if groups.len() > paired {
+            groups.split_off(paired)
+        } else {
+            remainder.split_off(paired)
+        };
+        for (group, tail) in groups.iter_mut().zip(remainder) {
+            group.extend(tail);
+        }
+        remainder = leftover;
+    }
+
+    groups.into_iter().chain(remainder).flatten().collect()
+}
+
+impl Grid {
+    /// Replace a row with a Euclidean distribution spanning the full grid width
+    pub fn fill_euclidean_row(&mut self, y: usize, hits: usize, rotation: usize) {
+        let pattern = euclidean(hits, self.width(), rotation);
+        for (x, value) in pattern.into_iter().enumerate() {
+            self.set(x, y, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequencer::fixtures::render;
+
+    #[test]
+    fn test_euclidean_patterns() {
+        assert_eq!(render(&euclidean(3, 8, 0)), "x..x..x.");
+        assert_eq!(render(&euclidean(5, 8, 0)), "x.xx.xx.");
+        assert_eq!(render(&euclidean(3, 7, 0)), "x.x.x..");
+        assert_eq!(render(&euclidean(5, 12, 0)), "x..x.x..x.x.");
+        assert_eq!(render(&euclidean(4, 16, 0)), "x...x...x...x...");
+        assert_eq!(render(&euclidean(0, 4, 0)), "....");
+        assert_eq!(render(&euclidean(9, 4, 0)), "xxxx");
+    }
+
+    #[test]
+    fn test_euclidean_rotation() {
+        assert_eq!(render(&euclidean(3, 8, 1)), ".x..x..x");
+        assert_eq!(render(&euclidean(3, 8, 9)), ".x..x..x");
+    }
+
+    #[test]
+    fn test_fill_euclidean_row() {
+        let mut grid = Grid::new(8, 2);
+        grid.fill_euclidean_row(1, 3, 0);
+        let row: Vec<bool> = (0..8).map(|x| grid.get(x, 1)).collect();
+        assert_eq!(row, euclidean(3, 8, 0));
+        assert!(grid.get(1, 0));
+    }
+}
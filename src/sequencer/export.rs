@@ -0,0 +1,185 @@
+//! MIDI file export - one pass through the arrangement, played by the same
+//! lanes as the playback engine against a virtual clock
+use super::meter::TICKS_PER_QUARTER;
+use super::playback::{lanes_for, play_lanes, PlaybackEvent};
+use super::{EventScheduler, PatternBank, Song};
+use crate::midi::scale::Key;
+use crate::midi::smf::{write_smf, SmfNoteSpan, SmfTempo};
+use std::time::{Duration, Instant};
+
+/// Exported files use ten ticks per sequencer tick, so microtiming and
+/// groove offsets finer than a sequencer tick survive
+const FILE_TICKS_PER_TICK: u64 = 10;
+pub const SMF_TICKS_PER_QUARTER: u16 = TICKS_PER_QUARTER as u16 * FILE_TICKS_PER_TICK as u16;
+
+/// One sequencer tick on the render clock
+const TICK: Duration = Duration::from_millis(1);
+
+impl Song {
+    /// A Standard MIDI File of the arrangement's notes and tempo map, starting
+    /// at `bpm`. Probabilities and random directions draw from `rng`, and fill
+    /// conditions play as if fill is off.
+    pub fn to_smf(
+        &self,
+        bank: &PatternBank,
+        key: &Key,
+        bpm: f32,
+        rng: &mut fastrand::Rng,
+    ) -> Vec<u8> {
+        let tempos: Vec<SmfTempo> = self
+            .tempo_map(bank, bpm)
+            .into_iter()
+            .map(|tempo| SmfTempo {
+                tick: tempo.tick * FILE_TICKS_PER_TICK,
+                ..tempo
+            })
+            .collect();
+        write_smf(
+            SMF_TICKS_PER_QUARTER,
+            &tempos,
+            &self.render_notes(bank, key, rng),
+        )
+    }
+
+    /// Every note of one pass through the arrangement, in file ticks on the
+    /// same timeline as `tempo_map`
+    pub fn render_notes(
+        &self,
+        bank: &PatternBank,
+        key: &Key,
+        rng: &mut fastrand::Rng,
+    ) -> Vec<SmfNoteSpan> {
+        let mut scheduler = EventScheduler::new();
+        // Early nudges can start the first note before the song does
+        let origin = Instant::now() + TICK * TICKS_PER_QUARTER * 4;
+        let mut step_start = origin;
+        let mut lanes = Vec::new();
+        let mut playing = None;
+
+        for entry in self.entries() {
+            let Some(grid) = bank.pattern(entry.pattern) else {
+                continue;
+            };
+            if playing != Some(entry.pattern) {
+                lanes = lanes_for(grid);
+                playing = Some(entry.pattern);
+            }
+            let step_duration = TICK * grid.resolution().step_ticks();
+            for _ in 0..entry.repeats * grid.loop_length() {
+                play_lanes(
+                    &mut lanes,
+                    grid,
+                    &mut scheduler,
+                    key,
+                    step_start,
+                    step_duration,
+                    rng,
+                );
+                step_start += step_duration;
+            }
+        }
+
+        // Pair each note-on with the next note-off of its pitch
+        let mut sounding: Vec<(u8, u64, u8)> = Vec::new(); // note, start tick, velocity
+        let mut notes = Vec::new();
+        for (at, event) in scheduler.drain() {
+            let tick = file_tick(origin, at);
+            match event {
+                PlaybackEvent::NoteOn(note, velocity) | PlaybackEvent::SlideTo(note, velocity) => {
+                    sounding.push((note, tick, velocity));
+                }
+                PlaybackEvent::NoteOff(note) => {
+                    if let Some(index) = sounding.iter().position(|&(n, _, _)| n == note) {
+                        let (_, start, velocity) = sounding.remove(index);
+                        notes.push(SmfNoteSpan {
+                            tick: start,
+                            length: tick - start,
+                            note,
+                            velocity,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        notes.sort_by_key(|note| note.tick);
+        notes
+    }
+}
+
+/// File tick of an instant on the render clock; nothing is earlier than 0
+fn file_tick(origin: Instant, at: Instant) -> u64 {
+    let ticks = at.saturating_duration_since(origin).as_secs_f64() / TICK.as_secs_f64();
+    (ticks * FILE_TICKS_PER_TICK as f64).round() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi::scale::Scale;
+    use crate::midi::smf::read_notes;
+    use crate::sequencer::{SongEntry, Step, TempoChange};
+
+    #[test]
+    fn test_smf_holds_notes_and_tempo_map() {
+        let mut bank = PatternBank::new(2, 4, 1);
+        for x in [1, 3] {
+            bank.pattern_mut(0).unwrap().set(x, 0, false);
+        }
+        let grid = bank.pattern_mut(1).unwrap();
+        grid.step_mut(0, 0).unwrap().degree = 2;
+        grid.step_mut(1, 0).unwrap().tie = true;
+        grid.set(2, 0, false);
+        grid.set(3, 0, false);
+
+        let mut song = Song::new();
+        song.push(SongEntry::new(0, 2));
+        song.push(SongEntry::new(1, 1).with_tempo(TempoChange::new(100.0)));
+        let key = Key::new(60, Scale::Major);
+        let bytes = song.to_smf(&bank, &key, 120.0, &mut fastrand::Rng::with_seed(1));
+
+        // Sixteenths are 240 ticks, and the tie holds the E into a second one
+        let smf = read_notes(&bytes).unwrap();
+        assert_eq!(smf.ticks_per_quarter, 960);
+        let starts: Vec<(u64, u8)> = smf.notes.iter().map(|n| (n.tick, n.note)).collect();
+        assert_eq!(
+            starts,
+            vec![(0, 60), (480, 60), (960, 60), (1440, 60), (1920, 64)]
+        );
+        assert_eq!(
+            smf.tempos,
+            vec![
+                SmfTempo {
+                    tick: 0,
+                    bpm: 120.0
+                },
+                SmfTempo {
+                    tick: 1920,
+                    bpm: 100.0
+                }
+            ]
+        );
+        let tied = song.render_notes(&bank, &key, &mut fastrand::Rng::with_seed(1));
+        assert_eq!(tied.last().map(|n| (n.tick, n.length)), Some((1920, 360)));
+        assert!(smf.notes.iter().all(|n| n.velocity == 100));
+    }
+
+    #[test]
+    fn test_smf_keeps_nudges() {
+        let mut bank = PatternBank::new(1, 4, 1);
+        let grid = bank.pattern_mut(0).unwrap();
+        grid.step_mut(1, 0).unwrap().nudge = 24;
+        grid.step_mut(2, 0).unwrap().nudge = -Step::MAX_NUDGE;
+        grid.set(3, 0, false);
+
+        let mut song = Song::new();
+        song.push(SongEntry::new(0, 1));
+        let key = Key::new(60, Scale::Chromatic);
+        let bytes = song.to_smf(&bank, &key, 120.0, &mut fastrand::Rng::with_seed(1));
+
+        // A quarter of a 240-tick step late, then half a step early
+        let smf = read_notes(&bytes).unwrap();
+        let ticks: Vec<u64> = smf.notes.iter().map(|n| n.tick).collect();
+        assert_eq!(ticks, vec![0, 300, 360]);
+    }
+}
@@ -0,0 +1,159 @@
+//! Groove templates - per-step timing and velocity offsets applied to a pattern
+use crate::midi::smf;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Groove {
+    name: String,
+    /// Timing offset per step, as a fraction of a step (-0.5 to 0.5)
+    timing: Vec<f32>,
+    /// Velocity multiplier per step (1.0 leaves velocity unchanged)
+    velocity: Vec<f32>,
+}
+
+impl Groove {
+    /// MPC-style swing amounts; 50% is straight, 66% is a triplet feel
+    pub const SWING_PRESETS: [u8; 6] = [54, 58, 62, 66, 71, 75];
+
+    pub fn new(name: &str, timing: Vec<f32>, velocity: Vec<f32>) -> Self {
+        let len = timing.len().max(velocity.len()).max(1);
+        let mut timing = timing;
+        let mut velocity = velocity;
+        timing.resize(len, 0.0);
+        velocity.resize(len, 1.0);
+
+        Self {
+            name: name.to_string(),
+            timing: timing.into_iter().map(|t| t.clamp(-0.5, 0.5)).collect(),
+            velocity: velocity.into_iter().map(|v| v.clamp(0.0, 2.0)).collect(),
+        }
+    }
+
+    /// MPC swing: every second step lands at `percent` of the way through its
+    /// pair, with the swung step slightly softer
+    pub fn swing(percent: u8) -> Self {
+        let percent = percent.clamp(50, 75) as f32;
+        let delay = 2.0 * percent / 100.0 - 1.0;
+        Self::new(
+            &format!("Swing {}%", percent),
+            vec![0.0, delay],
+            vec![1.0, 0.85],
+        )
+    }
+
+    /// Built-in grooves, in the order the GUI lists them
+    pub fn presets() -> Vec<Groove> {
+        Self::SWING_PRESETS
+            .iter()
+            .map(|&percent| Self::swing(percent))
+            .collect()
+    }
+
+    /// Extract a groove of `steps` 16th-note steps from a MIDI file
+    pub fn from_midi_file(path: impl AsRef<Path>, steps: usize) -> Result<Self, String> {
+        let path = path.as_ref();
+        let bytes =
+            std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "MIDI groove".to_string());
+
+        let mut groove = Self::from_midi_bytes(&bytes, steps)?;
+        groove.name = name;
+        Ok(groove)
+    }
+
+    /// Average how far each note sits from its nearest 16th, per step of the
+    /// template, and how loud each step is relative to the loudest one
+    pub fn from_midi_bytes(bytes: &[u8], steps: usize) -> Result<Self, String> {
+        let smf = smf::read_notes(bytes)?;
+        if smf.notes.is_empty() {
+            return Err("MIDI file contains no notes".to_string());
+        }
+
+        let steps = steps.max(1);
+        let ticks_per_step = (smf.ticks_per_quarter as f64 / 4.0).max(1.0);
+        let mut offsets = vec![Vec::new(); steps];
+        let mut velocities = vec![Vec::new(); steps];
+
+        for note in &smf.notes {
+            let position = note.tick as f64 / ticks_per_step;
+            let nearest = position.round();
+            let bucket = nearest as usize % steps;
+            offsets[bucket].push((position - nearest) as f32);
+            velocities[bucket].push(note.velocity as f32);
+        }
+
+        let average = |values: &Vec<f32>| {
+            (!values.is_empty()).then(|| values.iter().sum::<f32>() / values.len() as f32)
+        };
+        let loudest = velocities.iter().filter_map(average).fold(1.0, f32::max);
+
+        Ok(Self::new(
+            "MIDI groove",
+            offsets.iter().map(|o| average(o).unwrap_or(0.0)).collect(),
+            velocities
+                .iter()
+                .map(|v| average(v).map_or(1.0, |v| v / loudest))
+                .collect(),
+        ))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn len(&self) -> usize {
+        self.timing.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timing.is_empty()
+    }
+
+    /// Timing offset for a step index in fractions of a step, scaled by `strength` (0-1)
+    pub fn timing_offset(&self, step: usize, strength: f32) -> f32 {
+        self.timing[step % self.timing.len()] * strength.clamp(0.0, 1.0)
+    }
+
+    /// Velocity for a step index, blended towards the template by `strength` (0-1)
+    pub fn apply_velocity(&self, step: usize, velocity: u8, strength: f32) -> u8 {
+        let factor = self.velocity[step % self.velocity.len()];
+        let factor = 1.0 + (factor - 1.0) * strength.clamp(0.0, 1.0);
+        (velocity as f32 * factor).round().clamp(1.0, 127.0) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi::smf::format0_file;
+
+    #[test]
+    fn test_swing_delays_every_second_step() {
+        let groove = Groove::swing(66);
+        assert_eq!(groove.timing_offset(0, 1.0), 0.0);
+        assert!((groove.timing_offset(1, 1.0) - 0.32).abs() < 1e-6);
+        assert!((groove.timing_offset(3, 0.5) - 0.16).abs() < 1e-6);
+        assert_eq!(groove.apply_velocity(2, 100, 1.0), 100);
+        assert_eq!(groove.apply_velocity(1, 100, 0.0), 100);
+    }
+
+    #[test]
+    fn test_groove_from_midi_bytes() {
+        // 96 ticks per quarter = 24 ticks per 16th; second note is 6 ticks late and softer
+        let track: Vec<u8> = vec![
+            0x00, 0x90, 0x24, 0x64, //
+            0x1E, 0x90, 0x24, 0x32, //
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let bytes = format0_file(96, &track);
+
+        let groove = Groove::from_midi_bytes(&bytes, 2).unwrap();
+        assert_eq!(groove.len(), 2);
+        assert_eq!(groove.timing_offset(0, 1.0), 0.0);
+        assert!((groove.timing_offset(1, 1.0) - 0.25).abs() < 1e-6);
+        assert_eq!(groove.apply_velocity(1, 100, 1.0), 50);
+    }
+}
@@ -0,0 +1,498 @@
+//! Edit history - undo/redo of pattern, song, key and tempo edits as
+//! reversible commands
+use super::{Arrangement, Grid, PatternBank, Song, Step};
+use crate::midi::scale::Key;
+use std::collections::VecDeque;
+
+/// Everything an edit can change, borrowed from the sequencer while undoing
+pub struct EditTarget<'a> {
+    pub bank: &'a mut PatternBank,
+    pub song: &'a mut Song,
+    pub key: &'a mut Key,
+    pub bpm: &'a mut f32,
+}
+
+/// One step's contents before and after an edit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellChange {
+    pub x: usize,
+    pub y: usize,
+    pub before: Step,
+    pub after: Step,
+}
+
+#[derive(Debug, Clone)]
+pub enum EditCommand {
+    /// Step edits - toggles, step parameters, clears, fills - as per-cell changes
+    Cells {
+        pattern: usize,
+        changes: Vec<CellChange>,
+    },
+    /// Any other pattern change (settings, size), as whole-pattern snapshots
+    Pattern {
+        pattern: usize,
+        before: Box<Grid>,
+        after: Box<Grid>,
+    },
+    /// Song arrangement edits - entries, their tempo changes, the loop
+    Song {
+        before: Box<Arrangement>,
+        after: Box<Arrangement>,
+    },
+    /// Root note or scale changes
+    Key {
+        before: Key,
+        after: Key,
+    },
+    Tempo {
+        before: f32,
+        after: f32,
+    },
+}
+
+impl EditCommand {
+    /// The command turning `before` into `after`, or `None` if nothing changed
+    pub fn diff(pattern: usize, before: &Grid, after: &Grid) -> Option<Self> {
+        if before == after {
+            return None;
+        }
+
+        if before.width() == after.width() && before.height() == after.height() {
+            let mut changes = Vec::new();
+            for y in 0..before.height() {
+                for x in 0..before.width() {
+                    let (old, new) = (before.step(x, y), after.step(x, y));
+                    if old != new {
+                        changes.push(CellChange {
+                            x,
+                            y,
+                            before: old,
+                            after: new,
+                        });
+                    }
+                }
+            }
+
+            // Only steps changed if replaying them onto `before` gives `after`
+            let mut replayed = before.clone();
+            for change in &changes {
+                if let Some(step) = replayed.step_mut(change.x, change.y) {
+                    *step = change.after;
+                }
+            }
+            if replayed == *after {
+                return Some(EditCommand::Cells { pattern, changes });
+            }
+        }
+
+        Some(EditCommand::Pattern {
+            pattern,
+            before: Box::new(before.clone()),
+            after: Box::new(after.clone()),
+        })
+    }
+
+    /// The pattern a pattern edit applies to
+    pub fn pattern(&self) -> Option<usize> {
+        match self {
+            EditCommand::Cells { pattern, .. } | EditCommand::Pattern { pattern, .. } => {
+                Some(*pattern)
+            }
+            _ => None,
+        }
+    }
+
+    /// Redo the edit
+    pub fn apply(&self, target: &mut EditTarget) {
+        match self {
+            EditCommand::Cells { pattern, changes } => {
+                if let Some(grid) = target.bank.pattern_mut(*pattern) {
+                    for change in changes {
+                        if let Some(step) = grid.step_mut(change.x, change.y) {
+                            *step = change.after;
+                        }
+                    }
+                }
+            }
+            EditCommand::Pattern { pattern, after, .. } => {
+                if let Some(grid) = target.bank.pattern_mut(*pattern) {
+                    *grid = (**after).clone();
+                }
+            }
+            EditCommand::Song { after, .. } => target.song.set_arrangement((**after).clone()),
+            EditCommand::Key { after, .. } => *target.key = after.clone(),
+            EditCommand::Tempo { after, .. } => *target.bpm = *after,
+        }
+    }
+
+    /// Undo the edit
+    pub fn revert(&self, target: &mut EditTarget) {
+        match self {
+            EditCommand::Cells { pattern, changes } => {
+                if let Some(grid) = target.bank.pattern_mut(*pattern) {
+                    for change in changes.iter().rev() {
+                        if let Some(step) = grid.step_mut(change.x, change.y) {
+                            *step = change.before;
+                        }
+                    }
+                }
+            }
+            EditCommand::Pattern {
+                pattern, before, ..
+            } => {
+                if let Some(grid) = target.bank.pattern_mut(*pattern) {
+                    *grid = (**before).clone();
+                }
+            }
+            EditCommand::Song { before, .. } => target.song.set_arrangement((**before).clone()),
+            EditCommand::Key { before, .. } => *target.key = before.clone(),
+            EditCommand::Tempo { before, .. } => *target.bpm = *before,
+        }
+    }
+
+    /// True for a song, key or tempo command that ends where it started
+    fn is_noop(&self) -> bool {
+        match self {
+            EditCommand::Song { before, after } => before == after,
+            EditCommand::Key { before, after } => before == after,
+            EditCommand::Tempo { before, after } => before == after,
+            _ => false,
+        }
+    }
+
+    /// Fold a later command of the same kind into this one, keeping this
+    /// command's starting state; false if the kinds differ
+    fn absorb(&mut self, later: &EditCommand) -> bool {
+        match (self, later) {
+            (EditCommand::Song { after, .. }, EditCommand::Song { after: later, .. }) => {
+                *after = later.clone();
+            }
+            (EditCommand::Key { after, .. }, EditCommand::Key { after: later, .. }) => {
+                *after = later.clone();
+            }
+            (EditCommand::Tempo { after, .. }, EditCommand::Tempo { after: later, .. }) => {
+                *after = *later;
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Approximate size in steps, used to bound the history's memory
+    pub fn cost(&self) -> usize {
+        match self {
+            EditCommand::Cells { changes, .. } => changes.len().max(1),
+            EditCommand::Pattern { before, after, .. } => before.step_count() + after.step_count(),
+            EditCommand::Song { before, after } => {
+                (before.entries.len() + after.entries.len()).max(1)
+            }
+            EditCommand::Key { .. } | EditCommand::Tempo { .. } => 1,
+        }
+    }
+}
+
+/// Edits collected while a group is open
+#[derive(Debug, Clone, Default)]
+struct Group {
+    patterns: Vec<(usize, Grid)>, // patterns as they were when the group began
+    settings: Vec<EditCommand>,   // song, key and tempo commands, one per kind
+}
+
+/// Undo and redo stacks of edits, each edit being one or more commands.
+/// Oldest edits are dropped once either limit is exceeded.
+#[derive(Debug, Clone)]
+pub struct History {
+    undo: VecDeque<Vec<EditCommand>>,
+    redo: Vec<Vec<EditCommand>>,
+    max_edits: usize,
+    max_cost: usize,
+    group: Option<Group>,
+}
+
+impl History {
+    pub const DEFAULT_MAX_EDITS: usize = 256;
+    pub const DEFAULT_MAX_COST: usize = 64 * 1024;
+
+    pub fn new() -> Self {
+        Self::with_limits(Self::DEFAULT_MAX_EDITS, Self::DEFAULT_MAX_COST)
+    }
+
+    /// Keep at most `max_edits` edits totalling at most `max_cost` steps
+    pub fn with_limits(max_edits: usize, max_cost: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            max_edits: max_edits.max(1),
+            max_cost,
+            group: None,
+        }
+    }
+
+    /// Record a song, key or tempo command, folding it into the open group
+    /// if there is one
+    pub fn record_command(&mut self, command: EditCommand) {
+        if command.is_noop() {
+            return;
+        }
+        match &mut self.group {
+            Some(group) => {
+                if !group
+                    .settings
+                    .iter_mut()
+                    .any(|earlier| earlier.absorb(&command))
+                {
+                    group.settings.push(command);
+                }
+            }
+            None => self.record(vec![command]),
+        }
+    }
+
+    /// Like `record_command`, but fold the command into the latest undo entry
+    /// when that entry is a single command of the same kind, so one gesture
+    /// spread over several calls (such as a tap tempo sequence) undoes at once
+    pub fn record_continued(&mut self, command: EditCommand) {
+        if self.group.is_none() {
+            if let Some([last]) = self.undo.back_mut().map(Vec::as_mut_slice) {
+                if last.absorb(&command) {
+                    if last.is_noop() {
+                        self.undo.pop_back();
+                    }
+                    self.redo.clear();
+                    return;
+                }
+            }
+        }
+        self.record_command(command);
+    }
+
+    pub fn record(&mut self, edit: Vec<EditCommand>) {
+        if edit.is_empty() {
+            return;
+        }
+        self.undo.push_back(edit);
+        self.redo.clear();
+
+        while self.undo.len() > self.max_edits
+            || (self.undo.len() > 1 && self.cost() > self.max_cost)
+        {
+            self.undo.pop_front();
+        }
+    }
+
+    /// Steps held by the undo stack
+    pub fn cost(&self) -> usize {
+        self.undo.iter().flatten().map(EditCommand::cost).sum()
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn undo_len(&self) -> usize {
+        self.undo.len()
+    }
+
+    /// Revert the latest edit; false if there was nothing to undo
+    pub fn undo(&mut self, target: &mut EditTarget) -> bool {
+        let Some(edit) = self.undo.pop_back() else {
+            return false;
+        };
+        for command in edit.iter().rev() {
+            command.revert(target);
+        }
+        self.redo.push(edit);
+        true
+    }
+
+    /// Reapply the latest undone edit; false if there was nothing to redo
+    pub fn redo(&mut self, target: &mut EditTarget) -> bool {
+        let Some(edit) = self.redo.pop() else {
+            return false;
+        };
+        for command in &edit {
+            command.apply(target);
+        }
+        self.undo.push_back(edit);
+        true
+    }
+
+    /// Start collecting edits (e.g. a drag) into a single undo entry
+    pub fn begin_group(&mut self) {
+        if self.group.is_none() {
+            self.group = Some(Group::default());
+        }
+    }
+
+    pub fn is_grouping(&self) -> bool {
+        self.group.is_some()
+    }
+
+    /// Note a pattern's state before a grouped edit; only the first is kept
+    pub fn snapshot(&mut self, pattern: usize, before: &Grid) {
+        if let Some(group) = &mut self.group {
+            if !group.patterns.iter().any(|(index, _)| *index == pattern) {
+                group.patterns.push((pattern, before.clone()));
+            }
+        }
+    }
+
+    /// Close the group, recording everything it changed in `bank` as one edit
+    pub fn end_group(&mut self, bank: &PatternBank) {
+        let Some(group) = self.group.take() else {
+            return;
+        };
+        let mut edit: Vec<EditCommand> = group
+            .patterns
+            .iter()
+            .filter_map(|(index, before)| EditCommand::diff(*index, before, bank.pattern(*index)?))
+            .collect();
+        edit.extend(
+            group
+                .settings
+                .into_iter()
+                .filter(|command| !command.is_noop()),
+        );
+        self.record(edit);
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.group = None;
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi::scale::Scale;
+    use crate::sequencer::{Sequencer, SongEntry, TempoChange};
+
+    /// The engine's copy of the current pattern
+    fn shared_grid(seq: &Sequencer) -> Grid {
+        seq.grid_state().lock().unwrap().current().clone()
+    }
+
+    #[test]
+    fn test_undo_redo_toggle() {
+        let mut seq = Sequencer::new(4, 4);
+
+        seq.edit(|grid| grid.toggle(1, 2));
+        assert!(!seq.grid().get(1, 2));
+        assert!(matches!(
+            seq.history().undo.back().unwrap()[0],
+            EditCommand::Cells { .. }
+        ));
+
+        assert!(seq.undo());
+        assert!(seq.grid().get(1, 2));
+        assert_eq!(shared_grid(&seq), *seq.grid());
+        assert!(seq.redo());
+        assert!(!seq.grid().get(1, 2));
+        assert_eq!(shared_grid(&seq), *seq.grid());
+        assert!(!seq.redo());
+    }
+
+    #[test]
+    fn test_group_is_one_undo_entry() {
+        let mut seq = Sequencer::new(4, 4);
+
+        seq.begin_edit_group();
+        for x in 0..4 {
+            seq.edit(|grid| grid.toggle(x, 0));
+        }
+        seq.set_bpm(90.0);
+        seq.set_bpm(100.0);
+        seq.end_edit_group();
+        assert_eq!(seq.history().undo_len(), 1);
+
+        seq.undo();
+        assert!((0..4).all(|x| seq.grid().get(x, 0)));
+        assert_eq!(seq.bpm(), 120.0);
+        assert_eq!(shared_grid(&seq), *seq.grid());
+    }
+
+    #[test]
+    fn test_song_key_and_tempo_edits_undo() {
+        let mut seq = Sequencer::new(4, 4);
+        let tempo = Some(TempoChange::new(90.0));
+        seq.edit_song(|song| song.push(SongEntry::new(1, 2).with_tempo(TempoChange::new(90.0))));
+        seq.set_scale(Scale::Minor);
+        seq.set_note(62);
+        seq.set_bpm(140.0);
+        seq.set_current_bpm(150.0); // automation isn't an edit
+        assert_eq!(seq.history().undo_len(), 4);
+
+        seq.undo();
+        assert_eq!(seq.bpm(), 120.0);
+        seq.undo();
+        assert_eq!(seq.note(), 60);
+        seq.undo();
+        assert_eq!(*seq.key().scale(), Scale::Chromatic);
+        assert_eq!(*seq.key_state().lock().unwrap(), *seq.key());
+        seq.undo();
+        assert!(seq.song_state().lock().unwrap().is_empty());
+
+        seq.redo();
+        assert_eq!(seq.song_state().lock().unwrap().entries()[0].tempo, tempo);
+    }
+
+    #[test]
+    fn test_tap_sequence_is_one_undo_entry() {
+        use std::time::{Duration, Instant};
+
+        let mut seq = Sequencer::new(4, 4);
+        let start = Instant::now();
+        for tap in 0..8 {
+            seq.tap_tempo(start + Duration::from_millis(600 * tap));
+        }
+        assert!((seq.bpm() - 100.0).abs() < 0.01);
+        assert_eq!(seq.history().undo_len(), 1);
+
+        // Taps after the timeout start a new sequence
+        for tap in 0..4 {
+            seq.tap_tempo(start + Duration::from_millis(10_000 + 400 * tap));
+        }
+        assert!((seq.bpm() - 150.0).abs() < 0.01);
+        assert_eq!(seq.history().undo_len(), 2);
+
+        seq.undo();
+        assert!((seq.bpm() - 100.0).abs() < 0.01);
+        seq.undo();
+        assert_eq!(seq.bpm(), 120.0);
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let mut history = History::with_limits(3, usize::MAX);
+        for bpm in 0..4 {
+            let before = 100.0 + bpm as f32;
+            history.record_command(EditCommand::Tempo {
+                before,
+                after: before + 1.0,
+            });
+        }
+        assert_eq!(history.undo_len(), 3);
+
+        let full = Grid::new(4, 4);
+        let mut empty = full.clone();
+        empty.clear();
+        let mut history = History::with_limits(100, 20);
+        for _ in 0..4 {
+            history.record(vec![EditCommand::diff(0, &full, &empty).unwrap()]);
+            history.record(vec![EditCommand::diff(0, &empty, &full).unwrap()]);
+        }
+        assert!(history.cost() <= 20);
+    }
+}
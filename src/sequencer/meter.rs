@@ -0,0 +1,219 @@
+//! Musical time - time signatures, step resolution and bar/beat/step positions
+use std::time::Duration;
+
+/// Position resolution within a bar; divisible by every supported step resolution
+pub const TICKS_PER_QUARTER: u32 = 96;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeSignature {
+    beats: u32,
+    unit: u32,
+}
+
+impl TimeSignature {
+    pub const COMMON: TimeSignature = TimeSignature { beats: 4, unit: 4 };
+
+    pub const PRESETS: [TimeSignature; 8] = [
+        TimeSignature { beats: 2, unit: 4 },
+        TimeSignature { beats: 3, unit: 4 },
+        TimeSignature::COMMON,
+        TimeSignature { beats: 5, unit: 4 },
+        TimeSignature { beats: 5, unit: 8 },
+        TimeSignature { beats: 6, unit: 8 },
+        TimeSignature { beats: 7, unit: 8 },
+        TimeSignature { beats: 12, unit: 8 },
+    ];
+
+    /// `unit` must be a power of two from 1 to 32
+    pub fn new(beats: u32, unit: u32) -> Result<Self, String> {
+        if beats == 0 || beats > 32 {
+            return Err(format!("Invalid beats per bar: {}", beats));
+        }
+        if !unit.is_power_of_two() || unit > 32 {
+            return Err(format!("Invalid beat unit: {}", unit));
+        }
+        Ok(Self { beats, unit })
+    }
+
+    pub fn beats(&self) -> u32 {
+        self.beats
+    }
+
+    pub fn unit(&self) -> u32 {
+        self.unit
+    }
+
+    pub fn name(&self) -> String {
+        format!("{}/{}", self.beats, self.unit)
+    }
+
+    pub fn beat_ticks(&self) -> u32 {
+        TICKS_PER_QUARTER * 4 / self.unit
+    }
+
+    pub fn bar_ticks(&self) -> u32 {
+        self.beats * self.beat_ticks()
+    }
+
+    /// Beat length at `bpm` quarter notes per minute
+    pub fn beat_duration(&self, bpm: f32) -> Duration {
+        Duration::from_secs_f64(240.0 / bpm as f64 / self.unit as f64)
+    }
+}
+
+impl Default for TimeSignature {
+    fn default() -> Self {
+        Self::COMMON
+    }
+}
+
+/// Note value of one step
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Resolution {
+    Eighth,
+    EighthTriplet,
+    #[default]
+    Sixteenth,
+    SixteenthTriplet,
+    ThirtySecond,
+}
+
+impl Resolution {
+    pub const ALL: [Resolution; 5] = [
+        Resolution::Eighth,
+        Resolution::EighthTriplet,
+        Resolution::Sixteenth,
+        Resolution::SixteenthTriplet,
+        Resolution::ThirtySecond,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Resolution::Eighth => "1/8",
+            Resolution::EighthTriplet => "1/8T",
+            Resolution::Sixteenth => "1/16",
+            Resolution::SixteenthTriplet => "1/16T",
+            Resolution::ThirtySecond => "1/32",
+        }
+    }
+
+    pub fn steps_per_quarter(&self) -> u32 {
+        match self {
+            Resolution::Eighth => 2,
+            Resolution::EighthTriplet => 3,
+            Resolution::Sixteenth => 4,
+            Resolution::SixteenthTriplet => 6,
+            Resolution::ThirtySecond => 8,
+        }
+    }
+
+    pub fn step_ticks(&self) -> u32 {
+        TICKS_PER_QUARTER / self.steps_per_quarter()
+    }
+
+    /// Step length at `bpm` quarter notes per minute
+    pub fn step_duration(&self, bpm: f32) -> Duration {
+        Duration::from_secs_f64(60.0 / bpm as f64 / self.steps_per_quarter() as f64)
+    }
+}
+
+/// Zero-based bar, beat and step within the beat
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MusicalPosition {
+    pub bar: u32,
+    pub beat: u32,
+    pub step: u32,
+}
+
+impl MusicalPosition {
+    /// One-based "bar.beat.step", as shown on hardware sequencers
+    pub fn name(&self) -> String {
+        format!("{}.{}.{}", self.bar + 1, self.beat + 1, self.step + 1)
+    }
+}
+
+/// Running musical time, advanced step by step so meter changes take
+/// effect from the next bar line onwards without renumbering past bars
+#[derive(Debug, Clone, Default)]
+pub struct MusicalClock {
+    bar: u32,
+    bar_tick: u32,
+}
+
+impl MusicalClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn position(&self, signature: TimeSignature, resolution: Resolution) -> MusicalPosition {
+        let beat_ticks = signature.beat_ticks();
+        MusicalPosition {
+            bar: self.bar,
+            beat: self.bar_tick / beat_ticks,
+            step: self.bar_tick % beat_ticks / resolution.step_ticks(),
+        }
+    }
+
+    /// Whether the next step starts exactly on a beat
+    pub fn on_beat(&self, signature: TimeSignature) -> bool {
+        self.bar_tick.is_multiple_of(signature.beat_ticks())
+    }
+
+    pub fn advance(&mut self, signature: TimeSignature, resolution: Resolution) {
+        self.bar_tick += resolution.step_ticks();
+        while self.bar_tick >= signature.bar_ticks() {
+            self.bar_tick -= signature.bar_ticks();
+            self.bar += 1;
+        }
+    }
+
+    /// Move to the start of the next bar unless already on a bar line
+    pub fn next_bar(&mut self) {
+        if self.bar_tick > 0 {
+            self.bar_tick = 0;
+            self.bar += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_signature_validation() {
+        assert!(TimeSignature::new(7, 8).is_ok());
+        assert!(TimeSignature::new(0, 4).is_err());
+        assert!(TimeSignature::new(4, 3).is_err());
+        assert_eq!(TimeSignature::new(7, 8).unwrap().bar_ticks(), 7 * 48);
+    }
+
+    #[test]
+    fn test_clock_counts_bars_in_seven_eight() {
+        let signature = TimeSignature::new(7, 8).unwrap();
+        let resolution = Resolution::Sixteenth;
+        let mut clock = MusicalClock::new();
+
+        // 14 sixteenths per bar of 7/8
+        for _ in 0..15 {
+            clock.advance(signature, resolution);
+        }
+        let position = clock.position(signature, resolution);
+        assert_eq!(
+            position,
+            MusicalPosition {
+                bar: 1,
+                beat: 0,
+                step: 1
+            }
+        );
+        assert_eq!(position.name(), "2.1.2");
+    }
+
+    #[test]
+    fn test_triplet_step_duration() {
+        let duration = Resolution::EighthTriplet.step_duration(120.0);
+        assert_eq!(duration.as_micros(), 166_666);
+        assert_eq!(Resolution::Sixteenth.step_duration(120.0).as_millis(), 125);
+    }
+}
@@ -0,0 +1,60 @@
+//! Metronome - beat clicks and the count-in before playback
+use super::TimeSignature;
+use std::time::{Duration, Instant};
+
+/// How long a click note is held when sent over MIDI
+pub const CLICK_LENGTH: Duration = Duration::from_millis(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Metronome {
+    pub enabled: bool,
+    count_in_bars: u8,
+}
+
+impl Metronome {
+    pub const MAX_COUNT_IN_BARS: u8 = 2;
+
+    /// Bars of clicks played before the first step; 0 starts immediately
+    pub fn count_in_bars(&self) -> u8 {
+        self.count_in_bars
+    }
+
+    pub fn set_count_in_bars(&mut self, bars: u8) {
+        self.count_in_bars = bars.min(Self::MAX_COUNT_IN_BARS);
+    }
+
+    /// Click times of the count-in starting at `start`, each flagged when it
+    /// is a downbeat, and the time the first step should then play
+    pub fn count_in(
+        &self,
+        signature: TimeSignature,
+        bpm: f32,
+        start: Instant,
+    ) -> (Vec<(Instant, bool)>, Instant) {
+        let beat = signature.beat_duration(bpm);
+        let beats = self.count_in_bars as u32 * signature.beats();
+        let clicks = (0..beats)
+            .map(|i| (start + beat * i, i.is_multiple_of(signature.beats())))
+            .collect();
+        (clicks, start + beat * beats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_in_follows_time_signature() {
+        let mut metronome = Metronome::default();
+        metronome.set_count_in_bars(5);
+        assert_eq!(metronome.count_in_bars(), 2);
+
+        let start = Instant::now();
+        let signature = TimeSignature::new(3, 4).unwrap();
+        let (clicks, first_step) = metronome.count_in(signature, 120.0, start);
+        let accents: Vec<bool> = clicks.iter().map(|&(_, accent)| accent).collect();
+        assert_eq!(accents, vec![true, false, false, true, false, false]);
+        assert_eq!(first_step - start, Duration::from_secs(3));
+    }
+}
@@ -1,21 +1,83 @@
 /// Core sequencer logic - grid state and step management
 /// This is grid-agnostic and can work with any grid size
+use crate::midi::parse_note_name;
+use crate::midi::scale::{Key, Scale};
+use std::path::Path;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+pub mod bank;
+pub mod clipboard;
+pub mod condition;
+pub mod direction;
+pub mod euclid;
+pub mod export;
+pub mod groove;
+pub mod history;
+pub mod meter;
+pub mod metronome;
 pub mod playback;
-
-#[derive(Debug, Clone)]
+pub mod resize;
+pub mod scheduler;
+pub mod song;
+pub mod step;
+pub mod tap;
+pub mod tempo;
+pub mod track;
+pub mod transform;
+pub mod traversal;
+
+pub use bank::PatternBank;
+pub use clipboard::{Clip, PasteMode, Region};
+pub use condition::{Condition, TriggerContext};
+pub use direction::{Direction, Playhead};
+pub use groove::Groove;
+pub use history::{CellChange, EditCommand, EditTarget, History};
+pub use meter::{MusicalClock, MusicalPosition, Resolution, TimeSignature};
+pub use metronome::Metronome;
+pub use resize::ResizeMode;
+pub use scheduler::{EventScheduler, ScheduledNote, VelocityRamp};
+pub use song::{Arrangement, Song, SongEntry, SongPosition};
+pub use step::Step;
+pub use tap::TapTempo;
+pub use tempo::{TempoChange, TempoCurve, TempoRamp};
+pub use track::{Rate, Track};
+pub use traversal::Traversal;
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Grid {
-    cells: Vec<Vec<bool>>,
+    cells: Vec<Vec<Step>>,
     width: usize,
     height: usize,
+    direction: Direction,
+    traversal: Traversal,
+    groove: Option<Groove>,
+    groove_strength: f32,
+    tracks: Vec<Track>,
+    track_mode: bool,
+    time_signature: TimeSignature,
+    resolution: Resolution,
+    tempo_changes: Vec<(usize, TempoChange)>, // (loop step, change), sorted by step
 }
 
 impl Grid {
+    /// Largest grid a pattern can hold
+    pub const MAX_WIDTH: usize = Track::MAX_LENGTH;
+    pub const MAX_HEIGHT: usize = 16;
+
     pub fn new(width: usize, height: usize) -> Self {
         Self {
-            cells: vec![vec![true; width]; height],
+            cells: vec![vec![Step::new(true); width]; height],
             width,
             height,
+            direction: Direction::Forward,
+            traversal: Traversal::RowMajor,
+            groove: None,
+            groove_strength: 1.0,
+            tracks: vec![Track::new(width.max(1)); height],
+            track_mode: false,
+            time_signature: TimeSignature::default(),
+            resolution: Resolution::default(),
+            tempo_changes: Vec::new(),
         }
     }
 
@@ -27,19 +89,175 @@ impl Grid {
         self.height
     }
 
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    pub fn set_direction(&mut self, direction: Direction) {
+        self.direction = direction;
+    }
+
+    pub fn traversal(&self) -> &Traversal {
+        &self.traversal
+    }
+
+    pub fn set_traversal(&mut self, traversal: Traversal) {
+        self.traversal = traversal;
+    }
+
+    /// Number of steps in one pass of the pattern
+    pub fn step_count(&self) -> usize {
+        self.traversal.len(self.width, self.height)
+    }
+
+    /// Cell played at a step index - the single source of truth shared by
+    /// the playback engine and the GUI playhead
+    pub fn cell_at(&self, index: usize) -> Option<(usize, usize)> {
+        self.traversal.cell_at(index, self.width, self.height)
+    }
+
+    /// Step data played at a step index
+    pub fn step_at(&self, index: usize) -> Step {
+        self.cell_at(index)
+            .map(|(x, y)| self.step(x, y))
+            .unwrap_or_default()
+    }
+
+    /// In track mode every row is a track playing in parallel; otherwise the
+    /// grid plays as one sequence along its traversal
+    pub fn track_mode(&self) -> bool {
+        self.track_mode
+    }
+
+    pub fn set_track_mode(&mut self, track_mode: bool) {
+        self.track_mode = track_mode;
+    }
+
+    pub fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    pub fn track(&self, y: usize) -> Option<&Track> {
+        self.tracks.get(y)
+    }
+
+    pub fn track_mut(&mut self, y: usize) -> Option<&mut Track> {
+        self.tracks.get_mut(y)
+    }
+
+    /// Set a track's loop length, limited to the grid width so every step it
+    /// plays is a real cell
+    pub fn set_track_length(&mut self, y: usize, length: usize) {
+        let width = self.width.max(1);
+        if let Some(track) = self.tracks.get_mut(y) {
+            track.set_length(length.min(width));
+        }
+    }
+
+    /// Steps in one master loop: the traversal length, or the grid width in track mode
+    pub fn loop_length(&self) -> usize {
+        if self.track_mode {
+            self.width
+        } else {
+            self.step_count()
+        }
+    }
+
+    pub fn time_signature(&self) -> TimeSignature {
+        self.time_signature
+    }
+
+    pub fn set_time_signature(&mut self, time_signature: TimeSignature) {
+        self.time_signature = time_signature;
+    }
+
+    /// Note value of one step
+    pub fn resolution(&self) -> Resolution {
+        self.resolution
+    }
+
+    pub fn set_resolution(&mut self, resolution: Resolution) {
+        self.resolution = resolution;
+    }
+
+    /// Tempo changes that fire when the pattern loop reaches their step
+    pub fn tempo_changes(&self) -> &[(usize, TempoChange)] {
+        &self.tempo_changes
+    }
+
+    pub fn tempo_change_at(&self, step: usize) -> Option<TempoChange> {
+        self.tempo_changes
+            .iter()
+            .find(|(at, _)| *at == step)
+            .map(|&(_, change)| change)
+    }
+
+    /// Set or (with `None`) remove the tempo change on a loop step
+    pub fn set_tempo_change(&mut self, step: usize, change: Option<TempoChange>) {
+        self.tempo_changes.retain(|(at, _)| *at != step);
+        if let Some(change) = change {
+            self.tempo_changes.push((step, change));
+            self.tempo_changes.sort_by_key(|(at, _)| *at);
+        }
+    }
+
+    pub fn groove(&self) -> Option<&Groove> {
+        self.groove.as_ref()
+    }
+
+    pub fn set_groove(&mut self, groove: Option<Groove>) {
+        self.groove = groove;
+    }
+
+    /// How strongly the groove is applied, 0-1
+    pub fn groove_strength(&self) -> f32 {
+        self.groove_strength
+    }
+
+    pub fn set_groove_strength(&mut self, strength: f32) {
+        self.groove_strength = strength.clamp(0.0, 1.0);
+    }
+
+    /// Groove timing offset for a step index, in fractions of a step
+    pub fn groove_offset(&self, index: usize) -> f32 {
+        self.groove
+            .as_ref()
+            .map_or(0.0, |g| g.timing_offset(index, self.groove_strength))
+    }
+
+    /// Velocity for a step index after applying the groove
+    pub fn groove_velocity(&self, index: usize, velocity: u8) -> u8 {
+        self.groove.as_ref().map_or(velocity, |g| {
+            g.apply_velocity(index, velocity, self.groove_strength)
+        })
+    }
+
     pub fn get(&self, x: usize, y: usize) -> bool {
+        self.step(x, y).active
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: bool) {
+        if let Some(step) = self.step_mut(x, y) {
+            step.active = value;
+        }
+    }
+
+    /// Full step data; out-of-range cells read as an inactive default step
+    pub fn step(&self, x: usize, y: usize) -> Step {
         self.cells
             .get(y)
             .and_then(|row| row.get(x))
             .copied()
-            .unwrap_or(false)
+            .unwrap_or_default()
     }
 
-    pub fn set(&mut self, x: usize, y: usize, value: bool) {
-        if let Some(row) = self.cells.get_mut(y) {
-            if let Some(cell) = row.get_mut(x) {
-                *cell = value;
-            }
+    pub fn step_mut(&mut self, x: usize, y: usize) -> Option<&mut Step> {
+        self.cells.get_mut(y).and_then(|row| row.get_mut(x))
+    }
+
+    pub fn set_degree(&mut self, x: usize, y: usize, degree: i8) {
+        if let Some(step) = self.step_mut(x, y) {
+            step.degree = degree;
         }
     }
 
@@ -51,7 +269,7 @@ impl Grid {
     pub fn clear(&mut self) {
         for row in &mut self.cells {
             for cell in row {
-                *cell = false;
+                cell.active = false;
             }
         }
     }
@@ -59,76 +277,339 @@ impl Grid {
     pub fn fill(&mut self) {
         for row in &mut self.cells {
             for cell in row {
-                *cell = true;
+                cell.active = true;
             }
         }
     }
 }
 
 pub struct Sequencer {
-    grid: Grid,
-    grid_state: Arc<Mutex<Vec<Vec<bool>>>>,
-    current_position: usize,
+    bank: PatternBank,
+    grid_state: Arc<Mutex<PatternBank>>,
+    song_state: Arc<Mutex<Song>>,
+    playhead: Playhead,
+    rng: fastrand::Rng,
     bpm: f32,
-    note: u8,
+    tap_tempo: TapTempo,
+    history: History,
+    key: Key,
+    key_state: Arc<Mutex<Key>>,
     is_playing: bool,
 }
 
 impl Sequencer {
+    pub const MIN_BPM: f32 = 20.0;
+    pub const MAX_BPM: f32 = 999.0;
+
     pub fn new(width: usize, height: usize) -> Self {
-        let grid = Grid::new(width, height);
-        let initial_state = grid.cells.clone();
+        let bank = PatternBank::new(PatternBank::DEFAULT_SLOTS, width, height);
+        let initial_state = bank.clone();
 
         Self {
-            grid: Grid::new(width, height),
+            bank,
             grid_state: Arc::new(Mutex::new(initial_state)),
-            current_position: 0,
+            song_state: Arc::new(Mutex::new(Song::new())),
+            playhead: Playhead::new(),
+            rng: fastrand::Rng::new(),
             bpm: 120.0,
-            note: 60, // Middle C
+            tap_tempo: TapTempo::new(),
+            history: History::new(),
+            key: Key::new(60, Scale::Chromatic), // Middle C
+            key_state: Arc::new(Mutex::new(Key::new(60, Scale::Chromatic))),
             is_playing: false,
         }
     }
 
+    /// The pattern currently selected in the bank
     pub fn grid(&self) -> &Grid {
-        &self.grid
+        self.bank.current()
     }
 
-    pub fn grid_state(&self) -> &Arc<Mutex<Vec<Vec<bool>>>> {
+    /// Shared copy of the pattern bank read by the playback engine
+    pub fn grid_state(&self) -> &Arc<Mutex<PatternBank>> {
         &self.grid_state
     }
 
+    /// Direct access to the current pattern; changes made here skip the
+    /// undo history and the engine's copy, so prefer `edit`
     pub fn grid_mut(&mut self) -> &mut Grid {
-        &mut self.grid
+        self.bank.current_mut()
+    }
+
+    /// Shared song arrangement, walked by the playback engine when enabled
+    pub fn song_state(&self) -> &Arc<Mutex<Song>> {
+        &self.song_state
+    }
+
+    pub fn bank(&self) -> &PatternBank {
+        &self.bank
+    }
+
+    /// Switch patterns immediately
+    pub fn select_pattern(&mut self, index: usize) {
+        self.bank.select(index);
+        self.grid_state.lock().unwrap().select(index);
+    }
+
+    /// Switch patterns at the end of the current loop
+    pub fn queue_pattern(&mut self, index: usize) {
+        self.bank.queue(index);
+        self.grid_state.lock().unwrap().queue(index);
+    }
+
+    /// Mirror a pattern switch made by the playback engine
+    pub fn set_current_pattern(&mut self, index: usize) {
+        self.bank.select(index);
+    }
+
+    pub fn copy_pattern(&mut self, from: usize, to: usize) {
+        if let Some(source) = self.bank.pattern(from).cloned() {
+            self.edit_pattern(to, |grid| *grid = source);
+        }
+    }
+
+    pub fn clear_pattern(&mut self, index: usize) {
+        self.edit_pattern(index, Grid::clear);
+    }
+
+    /// Resize the current pattern as one undoable edit
+    pub fn resize(&mut self, width: usize, height: usize, mode: ResizeMode) {
+        self.edit(|grid| grid.resize(width, height, mode));
+    }
+
+    /// Apply an undoable edit to the current pattern
+    pub fn edit<R>(&mut self, f: impl FnOnce(&mut Grid) -> R) -> R {
+        let index = self.bank.current_index();
+        self.edit_pattern(index, f)
+            .expect("current pattern is always in the bank")
+    }
+
+    /// Apply an undoable edit to any pattern in the bank
+    pub fn edit_pattern<R>(&mut self, index: usize, f: impl FnOnce(&mut Grid) -> R) -> Option<R> {
+        let grid = self.bank.pattern_mut(index)?;
+        let before = grid.clone();
+        let result = f(grid);
+
+        if self.history.is_grouping() {
+            self.history.snapshot(index, &before);
+        } else if let Some(command) = EditCommand::diff(index, &before, grid) {
+            self.history.record(vec![command]);
+        }
+        self.update_grid_state();
+        Some(result)
+    }
+
+    /// Group the following edits into one undo entry until `end_edit_group`
+    pub fn begin_edit_group(&mut self) {
+        self.history.begin_group();
+    }
+
+    pub fn end_edit_group(&mut self) {
+        self.history.end_group(&self.bank);
+    }
+
+    pub fn is_edit_group_open(&self) -> bool {
+        self.history.is_grouping()
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    pub fn undo(&mut self) -> bool {
+        self.step_history(false)
+    }
+
+    pub fn redo(&mut self) -> bool {
+        self.step_history(true)
+    }
+
+    fn step_history(&mut self, redo: bool) -> bool {
+        self.end_edit_group();
+        let mut song = self.song_state.lock().unwrap();
+        let mut target = EditTarget {
+            bank: &mut self.bank,
+            song: &mut song,
+            key: &mut self.key,
+            bpm: &mut self.bpm,
+        };
+        let changed = if redo {
+            self.history.redo(&mut target)
+        } else {
+            self.history.undo(&mut target)
+        };
+        drop(song);
+
+        self.update_grid_state();
+        self.update_key_state();
+        changed
+    }
+
+    /// Apply an undoable edit to the song arrangement
+    pub fn edit_song<R>(&mut self, f: impl FnOnce(&mut Song) -> R) -> R {
+        let mut song = self.song_state.lock().unwrap();
+        let before = song.arrangement();
+        let result = f(&mut song);
+        let after = song.arrangement();
+        drop(song);
+
+        self.history.record_command(EditCommand::Song {
+            before: Box::new(before),
+            after: Box::new(after),
+        });
+        result
     }
 
     pub fn current_position(&self) -> usize {
-        self.current_position
+        self.playhead.position()
     }
 
     pub fn set_current_position(&mut self, pos: usize) {
-        self.current_position = pos;
+        self.playhead.set_position(pos);
     }
 
+    /// Move to the next step following the pattern's direction
     pub fn advance_position(&mut self) -> usize {
-        let total_steps = self.grid.width() * self.grid.height();
-        self.current_position = (self.current_position + 1) % total_steps;
-        self.current_position
+        let total_steps = self.grid().step_count();
+        let direction = self.grid().direction();
+        self.playhead.advance(direction, total_steps, &mut self.rng)
+    }
+
+    pub fn set_direction(&mut self, direction: Direction) {
+        self.edit(|grid| grid.set_direction(direction));
+    }
+
+    pub fn set_traversal(&mut self, traversal: Traversal) {
+        self.edit(|grid| grid.set_traversal(traversal));
+    }
+
+    pub fn set_groove(&mut self, groove: Option<Groove>) {
+        self.edit(|grid| grid.set_groove(groove));
+    }
+
+    pub fn set_track_mode(&mut self, track_mode: bool) {
+        self.edit(|grid| grid.set_track_mode(track_mode));
+    }
+
+    pub fn set_track_length(&mut self, y: usize, length: usize) {
+        self.edit(|grid| grid.set_track_length(y, length));
+    }
+
+    pub fn set_track_direction(&mut self, y: usize, direction: Direction) {
+        self.edit(|grid| {
+            if let Some(track) = grid.track_mut(y) {
+                track.set_direction(direction);
+            }
+        });
+    }
+
+    pub fn set_track_rate(&mut self, y: usize, rate: Rate) {
+        self.edit(|grid| {
+            if let Some(track) = grid.track_mut(y) {
+                track.set_rate(rate);
+            }
+        });
+    }
+
+    /// Transpose the current pattern by semitones within the current key's scale
+    pub fn transpose_semitones(&mut self, semitones: i32) {
+        let scale = self.key.scale().clone();
+        self.edit(|grid| grid.transpose_semitones(semitones, &scale));
+    }
+
+    pub fn set_tempo_change(&mut self, step: usize, change: Option<TempoChange>) {
+        self.edit(|grid| grid.set_tempo_change(step, change));
+    }
+
+    pub fn set_time_signature(&mut self, time_signature: TimeSignature) {
+        self.edit(|grid| grid.set_time_signature(time_signature));
+    }
+
+    pub fn set_resolution(&mut self, resolution: Resolution) {
+        self.edit(|grid| grid.set_resolution(resolution));
+    }
+
+    pub fn set_groove_strength(&mut self, strength: f32) {
+        self.edit(|grid| grid.set_groove_strength(strength));
     }
 
     pub fn bpm(&self) -> f32 {
         self.bpm
     }
 
+    /// Tempo in quarter notes per minute; fractional tempos like 128.5 are kept
     pub fn set_bpm(&mut self, bpm: f32) {
-        self.bpm = bpm.clamp(40.0, 240.0);
+        let before = self.bpm;
+        self.set_current_bpm(bpm);
+        self.history.record_command(EditCommand::Tempo {
+            before,
+            after: self.bpm,
+        });
     }
 
+    /// Mirror a tempo reached by playback automation, outside the undo history
+    pub fn set_current_bpm(&mut self, bpm: f32) {
+        self.bpm = bpm.clamp(Self::MIN_BPM, Self::MAX_BPM);
+    }
+
+    /// Register a tap-tempo tap; once the estimate settles it becomes the BPM
+    pub fn tap_tempo(&mut self, at: Instant) -> Option<f32> {
+        let bpm = self.tap_tempo.tap(at)?;
+        let before = self.bpm;
+        self.set_current_bpm(bpm);
+        let command = EditCommand::Tempo {
+            before,
+            after: self.bpm,
+        };
+        // Later taps refine the first estimate, so a sequence is one undo entry
+        if self.tap_tempo.tap_count() > 2 {
+            self.history.record_continued(command);
+        } else {
+            self.history.record_command(command);
+        }
+        Some(self.bpm)
+    }
+
+    /// Root note of the key; steps are played as scale degrees above it
     pub fn note(&self) -> u8 {
-        self.note
+        self.key.root()
     }
 
     pub fn set_note(&mut self, note: u8) {
-        self.note = note.clamp(0, 127);
+        let mut key = self.key.clone();
+        key.set_root(note);
+        self.set_key(key);
+    }
+
+    /// Set the root from a note name like "F#2" or "Bb3"
+    pub fn set_note_name(&mut self, name: &str) -> Result<(), String> {
+        let note = parse_note_name(name)?;
+        self.set_note(note);
+        Ok(())
+    }
+
+    pub fn key(&self) -> &Key {
+        &self.key
+    }
+
+    pub fn set_key(&mut self, key: Key) {
+        let before = std::mem::replace(&mut self.key, key);
+        let after = self.key.clone();
+        self.history
+            .record_command(EditCommand::Key { before, after });
+        self.update_key_state();
+    }
+
+    pub fn set_scale(&mut self, scale: Scale) {
+        let mut key = self.key.clone();
+        key.set_scale(scale);
+        self.set_key(key);
+    }
+
+    /// MIDI note a step plays, with its degree quantized to the current key
+    pub fn note_at(&self, x: usize, y: usize) -> u8 {
+        self.key
+            .note_for_degree(self.grid().step(x, y).degree as i32)
     }
 
     pub fn is_playing(&self) -> bool {
@@ -149,27 +630,77 @@ impl Sequencer {
 
     /// Check if the current step should trigger a note
     pub fn should_trigger(&self) -> bool {
-        let total_steps = self.grid.width() * self.grid.height();
-        if self.current_position >= total_steps {
-            return false;
-        }
-
-        let x = self.current_position % self.grid.width();
-        let y = self.current_position / self.grid.width();
-        self.grid.get(x, y)
+        self.grid().step_at(self.current_position()).active
     }
 
     /// Calculate step duration in milliseconds
-    pub fn step_duration_ms(&self) -> u64 {
-        let steps_per_beat = 4.0; // 16th notes
-        let beats_per_second = self.bpm / 60.0;
-        let steps_per_second = beats_per_second * steps_per_beat;
-        (1000.0 / steps_per_second) as u64
+    pub fn step_duration_ms(&self) -> f64 {
+        self.step_duration().as_secs_f64() * 1000.0
+    }
+
+    /// Step length at nanosecond precision, so fractional tempos don't drift
+    pub fn step_duration(&self) -> Duration {
+        self.grid().resolution().step_duration(self.bpm)
+    }
+
+    /// Write the song - or the current pattern when the song is empty - as a
+    /// MIDI file of its notes and tempo automation
+    pub fn export_smf(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let song = self.song_state.lock().unwrap();
+        let mut rng = fastrand::Rng::new();
+        let bytes = if song.is_empty() {
+            let mut single = Song::new();
+            single.push(SongEntry::new(self.bank.current_index(), 1));
+            single.to_smf(&self.bank, &self.key, self.bpm, &mut rng)
+        } else {
+            song.to_smf(&self.bank, &self.key, self.bpm, &mut rng)
+        };
+
+        let path = path.as_ref();
+        std::fs::write(path, bytes)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// Shared copy of the key read by the playback engine, so root and scale
+    /// changes apply while playing
+    pub fn key_state(&self) -> &Arc<Mutex<Key>> {
+        &self.key_state
+    }
+
+    fn update_key_state(&mut self) {
+        *self.key_state.lock().unwrap() = self.key.clone();
     }
 
     pub fn update_grid_state(&mut self) {
         let mut shared = self.grid_state.lock().unwrap();
-        *shared = self.grid.cells.clone();
+        shared.sync_patterns_from(&self.bank);
+    }
+}
+
+/// Grid fixtures shared by the editing modules' tests
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use super::Grid;
+
+    /// Hits as `x` and rests as `.`
+    pub fn render(pattern: &[bool]) -> String {
+        pattern
+            .iter()
+            .map(|&on| if on { 'x' } else { '.' })
+            .collect()
+    }
+
+    /// One grid row, rendered like `render`
+    pub fn row(grid: &Grid, y: usize) -> String {
+        let pattern: Vec<bool> = (0..grid.width()).map(|x| grid.get(x, y)).collect();
+        render(&pattern)
+    }
+
+    /// Set a row from `x`/`.` text
+    pub fn set_row(grid: &mut Grid, y: usize, cells: &str) {
+        for (x, c) in cells.chars().enumerate() {
+            grid.set(x, y, c == 'x');
+        }
     }
 }
 
@@ -199,4 +730,42 @@ mod tests {
         seq.advance_position();
         assert_eq!(seq.current_position(), 1);
     }
+
+    #[test]
+    fn test_track_lengths() {
+        let mut grid = Grid::new(8, 4);
+        assert_eq!(grid.loop_length(), 32);
+        grid.set_track_mode(true);
+        assert_eq!(grid.loop_length(), 8);
+
+        let track = grid.track_mut(1).unwrap();
+        track.set_length(5);
+        assert_eq!(track.length(), 5);
+        track.set_length(0);
+        assert_eq!(track.length(), 1);
+        track.set_length(1000);
+        assert_eq!(track.length(), Track::MAX_LENGTH);
+
+        grid.set_track_length(1, 1000);
+        assert_eq!(grid.track(1).unwrap().length(), 8);
+        grid.set_track_length(1, 3);
+        assert_eq!(grid.track(1).unwrap().length(), 3);
+    }
+
+    #[test]
+    fn test_fractional_bpm() {
+        let mut seq = Sequencer::new(4, 4);
+        seq.set_bpm(128.5);
+        assert_eq!(seq.bpm(), 128.5);
+        assert!((seq.step_duration_ms() - 116.731_517).abs() < 1e-5);
+
+        // A thousand steps land within a microsecond of the ideal time
+        let ideal = 1000.0 * 60.0 / 128.5 / 4.0;
+        assert!(((seq.step_duration() * 1000).as_secs_f64() - ideal).abs() < 1e-6);
+
+        seq.set_bpm(5.0);
+        assert_eq!(seq.bpm(), Sequencer::MIN_BPM);
+        seq.set_bpm(1200.0);
+        assert_eq!(seq.bpm(), Sequencer::MAX_BPM);
+    }
 }
@@ -1,20 +1,45 @@
-use std::sync::mpsc::{channel, Receiver, Sender};
 /// Playback engine - coordinates timing and triggers
+use super::metronome::CLICK_LENGTH;
+use super::scheduler::offset_by_steps;
+use super::tempo::clamp_bpm;
+use super::{
+    Direction, EventScheduler, Grid, Metronome, MusicalClock, MusicalPosition, PatternBank,
+    Playhead, Rate, Song, SongPosition, Step, TempoRamp, TriggerContext,
+};
+use crate::midi::scale::Key;
+use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 
+/// How long a sliding note overlaps the note it slides from
+const SLIDE_OVERLAP: Duration = Duration::from_millis(10);
+
 #[derive(Debug, Clone)]
 pub enum PlaybackEvent {
     StepAdvanced(usize),
-    NoteOn(u8, u8), // note, velocity
-    NoteOff(u8),    // note
+    NoteOn(u8, u8),                  // note, velocity
+    NoteOff(u8),                     // note
+    SlideTo(u8, u8), // note, velocity - legato note-on gliding from the previous note
+    TrackStepAdvanced(usize, usize), // track, step within that track's own loop
+    Position(MusicalPosition), // bar/beat/step of the step now starting
+    TempoChanged(f32), // bpm reached by tempo automation
+    Click(bool),     // metronome click, true on the downbeat
+    ClickOff,
+    PatternChanged(usize), // bank slot now playing
+    SongPositionChanged(SongPosition),
+    SongEnded,
 }
 
 pub struct PlaybackEngine {
     sender: Sender<PlaybackEvent>,
     receiver: Receiver<PlaybackEvent>,
     is_running: Arc<Mutex<bool>>,
+    fill: Arc<Mutex<bool>>,
+    metronome: Arc<Mutex<Metronome>>,
+    bpm: Arc<Mutex<f32>>,
+    tempo_nudge: Arc<Mutex<f32>>,
+    seed: Option<u64>,
 }
 
 impl PlaybackEngine {
@@ -25,16 +50,20 @@ impl PlaybackEngine {
             sender,
             receiver,
             is_running: Arc::new(Mutex::new(false)),
+            fill: Arc::new(Mutex::new(false)),
+            metronome: Arc::new(Mutex::new(Metronome::default())),
+            bpm: Arc::new(Mutex::new(120.0)),
+            tempo_nudge: Arc::new(Mutex::new(0.0)),
+            seed: None,
         }
     }
 
     pub fn start(
         &mut self,
         bpm: f32,
-        grid_width: usize,
-        grid_height: usize,
-        grid_state: Arc<Mutex<Vec<Vec<bool>>>>,
-        note: u8,
+        grid_state: Arc<Mutex<PatternBank>>,
+        song_state: Arc<Mutex<Song>>,
+        key_state: Arc<Mutex<Key>>,
     ) {
         if *self.is_running.lock().unwrap() {
             return;
@@ -43,49 +72,225 @@ impl PlaybackEngine {
         *self.is_running.lock().unwrap() = true;
 
         let is_running = Arc::clone(&self.is_running);
+        let fill = Arc::clone(&self.fill);
+        let metronome = Arc::clone(&self.metronome);
+        *self.bpm.lock().unwrap() = bpm;
+        let tempo = Arc::clone(&self.bpm);
+        let tempo_nudge = Arc::clone(&self.tempo_nudge);
+        let seed = self.seed;
         let sender = self.sender.clone();
 
+        // In song mode, start on the pattern at the song's current position
+        let mut initial_tempo = None;
+        {
+            let song = song_state.lock().unwrap();
+            if let (true, Some(pattern)) = (song.is_active(), song.current_pattern()) {
+                initial_tempo = song.entry_tempo();
+                grid_state.lock().unwrap().select(pattern);
+                let _ = sender.send(PlaybackEvent::PatternChanged(pattern));
+                let _ = sender.send(PlaybackEvent::SongPositionChanged(song.position()));
+            }
+        }
+
         thread::spawn(move || {
-            let step_duration = Duration::from_secs_f32(60.0 / bpm / 4.0);
-            let note_duration = step_duration / 2;
-            let total_steps = grid_width * grid_height;
-            let mut current_step = vec![0, 0];
-            let mut last_step_time = Instant::now();
+            let (signature, resolution) = {
+                let bank = grid_state.lock().unwrap();
+                (bank.current().time_signature(), bank.current().resolution())
+            };
+            let mut step_duration = resolution.step_duration(bpm);
+            let mut ramp = initial_tempo.map(|change| TempoRamp::new(bpm, change, signature));
+            let mut clock = MusicalClock::new();
+            let mut scheduler = EventScheduler::new();
+            let mut rng = seed.map_or_else(fastrand::Rng::new, fastrand::Rng::with_seed);
+            let mut lanes = lanes_for(grid_state.lock().unwrap().current());
+            let mut steps_in_loop = 0;
+            let mut song_ending = false;
+
+            // Steps are scheduled a master step ahead of when they sound, so ties
+            // and slides can still reach the previous note's pending note-off and
+            // early microtiming nudges are never in the past
+            let start = Instant::now() + step_duration;
+
+            // Count-in clicks play before the first step
+            let count_in = *metronome.lock().unwrap();
+            let (clicks, mut next_step_time) = count_in.count_in(signature, bpm, start);
+            for (at, accent) in clicks {
+                scheduler.schedule(at, PlaybackEvent::Click(accent));
+                scheduler.schedule(at + CLICK_LENGTH, PlaybackEvent::ClickOff);
+            }
 
             while *is_running.lock().unwrap() {
-                let now = Instant::now();
+                if !song_ending && Instant::now() + step_duration >= next_step_time {
+                    let step_start = next_step_time;
+                    let fill = *fill.lock().unwrap();
+                    let key = key_state.lock().unwrap().clone();
+
+                    // Play every lane step that starts within this master step
+                    let loop_length = {
+                        let bank_lock = grid_state.lock().unwrap();
+                        let grid_lock = bank_lock.current();
+                        if lanes.len() != lane_count(grid_lock) {
+                            lanes = lanes_for(grid_lock);
+                        }
+
+                        // The pattern's meter sets the step length and musical position
+                        let (signature, resolution) =
+                            (grid_lock.time_signature(), grid_lock.resolution());
 
-                if now.duration_since(last_step_time) >= step_duration {
-                    // Notify that step advanced
-                    let _ = sender.send(PlaybackEvent::StepAdvanced(current_step));
+                        // A tempo change on this loop step starts a new ramp from here
+                        if let Some(change) = grid_lock.tempo_change_at(steps_in_loop) {
+                            let from = *tempo.lock().unwrap();
+                            ramp = Some(TempoRamp::new(from, change, signature));
+                        }
+                        let nudge = 1.0 + *tempo_nudge.lock().unwrap();
+                        step_duration = match ramp.as_mut() {
+                            Some(active) => {
+                                let duration = active.advance(resolution.step_ticks());
+                                let bpm = clamp_bpm(active.bpm());
+                                *tempo.lock().unwrap() = bpm;
+                                let event = PlaybackEvent::TempoChanged(bpm);
+                                scheduler.schedule(step_start + duration, event);
+                                duration.div_f32(nudge)
+                            }
+                            None => resolution.step_duration(*tempo.lock().unwrap() * nudge),
+                        };
+                        if ramp.as_ref().is_some_and(TempoRamp::is_finished) {
+                            ramp = None;
+                        }
+                        next_step_time += step_duration;
+                        let position = clock.position(signature, resolution);
+                        scheduler.schedule(step_start, PlaybackEvent::Position(position));
+                        if metronome.lock().unwrap().enabled && clock.on_beat(signature) {
+                            let accent = position.beat == 0;
+                            scheduler.schedule(step_start, PlaybackEvent::Click(accent));
+                            scheduler.schedule(step_start + CLICK_LENGTH, PlaybackEvent::ClickOff);
+                        }
+                        clock.advance(signature, resolution);
 
-                    // Check if step should trigger
-                    let should_trigger = {
-                        let grid_lock = grid_state.lock().unwrap();
-                        current_step < grid_lock.len() && grid_lock[current_step]
+                        if grid_lock.track_mode() {
+                            let event = PlaybackEvent::StepAdvanced(steps_in_loop);
+                            scheduler.schedule(step_start, event);
+                        }
+
+                        for lane in lanes.iter_mut() {
+                            lane.context.fill = fill;
+                        }
+                        play_lanes(
+                            &mut lanes,
+                            grid_lock,
+                            &mut scheduler,
+                            &key,
+                            step_start,
+                            step_duration,
+                            &mut rng,
+                        );
+
+                        grid_lock.loop_length()
                     };
 
-                    if should_trigger {
-                        // Send note on
-                        let _ = sender.send(PlaybackEvent::NoteOn(note, 100));
+                    // A loop is one pass worth of steps, whatever the direction
+                    steps_in_loop += 1;
+
+                    // Song advances and queued pattern switches land on the loop boundary
+                    if steps_in_loop >= loop_length {
+                        steps_in_loop = 0;
+                        let mut pattern_changed = None;
+                        let mut song = song_state.lock().unwrap();
+                        let mut bank = grid_state.lock().unwrap();
+                        if song.is_active() {
+                            match song.advance() {
+                                Some(position) => {
+                                    if let Some(pattern) = song.current_pattern() {
+                                        if pattern != bank.current_index() {
+                                            bank.select(pattern);
+                                            pattern_changed = Some(pattern);
+                                        }
+                                    }
+                                    if let Some(change) = song.entry_tempo() {
+                                        let from = *tempo.lock().unwrap();
+                                        let signature = bank.current().time_signature();
+                                        ramp = Some(TempoRamp::new(from, change, signature));
+                                    }
+                                    scheduler.schedule(
+                                        next_step_time,
+                                        PlaybackEvent::SongPositionChanged(position),
+                                    );
+                                }
+                                None => {
+                                    scheduler.schedule(next_step_time, PlaybackEvent::SongEnded);
+                                    song_ending = true;
+                                }
+                            }
+                        } else {
+                            pattern_changed = bank.apply_queued();
+                        }
 
-                        // Schedule note off
-                        let sender_clone = sender.clone();
-                        thread::spawn(move || {
-                            thread::sleep(note_duration);
-                            let _ = sender_clone.send(PlaybackEvent::NoteOff(note));
-                        });
+                        if let Some(index) = pattern_changed {
+                            lanes = lanes_for(bank.current());
+                            clock.next_bar();
+                            scheduler
+                                .schedule(next_step_time, PlaybackEvent::PatternChanged(index));
+                        }
                     }
+                }
 
-                    current_step = (current_step + 1) % total_steps;
-                    last_step_time = now;
+                for event in scheduler.due(Instant::now()) {
+                    let ended = matches!(event, PlaybackEvent::SongEnded);
+                    let _ = sender.send(event);
+                    if ended {
+                        *is_running.lock().unwrap() = false;
+                    }
                 }
 
                 thread::sleep(Duration::from_millis(1));
             }
+
+            // Release anything still sounding when playback stops
+            for event in scheduler.flush_note_offs() {
+                let _ = sender.send(event);
+            }
         });
     }
 
+    /// Seed the engine's random number generator so probabilities and
+    /// random directions repeat exactly between runs. `None` seeds randomly.
+    pub fn set_seed(&mut self, seed: Option<u64>) {
+        self.seed = seed;
+    }
+
+    /// Enable fill mode for `Fill` / `NotFill` conditions, live while playing
+    pub fn set_fill(&mut self, fill: bool) {
+        *self.fill.lock().unwrap() = fill;
+    }
+
+    pub fn is_fill(&self) -> bool {
+        *self.fill.lock().unwrap()
+    }
+
+    /// Change the tempo while playing, from the next step on
+    pub fn set_bpm(&mut self, bpm: f32) {
+        *self.bpm.lock().unwrap() = clamp_bpm(bpm);
+    }
+
+    /// Temporarily speed up (positive) or slow down (negative) by a fraction
+    /// of the tempo, for beat-matching by ear; set back to 0 on release
+    pub fn set_tempo_nudge(&mut self, amount: f32) {
+        *self.tempo_nudge.lock().unwrap() = amount.clamp(-0.5, 0.5);
+    }
+
+    pub fn tempo_nudge(&self) -> f32 {
+        *self.tempo_nudge.lock().unwrap()
+    }
+
+    /// Metronome settings, live while playing; the count-in applies on start
+    pub fn set_metronome(&mut self, metronome: Metronome) {
+        *self.metronome.lock().unwrap() = metronome;
+    }
+
+    pub fn metronome(&self) -> Metronome {
+        *self.metronome.lock().unwrap()
+    }
+
     pub fn stop(&mut self) {
         *self.is_running.lock().unwrap() = false;
     }
@@ -103,8 +308,298 @@ impl PlaybackEngine {
     }
 }
 
+/// What a lane plays on one tick, read while holding the grid lock
+struct LaneStep {
+    step: Step,
+    length: usize,
+    direction: Direction,
+    groove_offset: f32,
+    velocity: u8,
+}
+
+/// Playback state for one lane: the whole grid in sequence mode, or one
+/// row in track mode, each looping over its own length
+pub(super) struct Lane {
+    playhead: Playhead,
+    rate: Rate,
+    clock: u32, // next step's offset into the master step, in 1/rate.steps() units
+    steps_in_loop: usize,
+    context: TriggerContext,
+    last_note: Option<u8>,
+}
+
+impl Lane {
+    fn new(direction: Direction, length: usize, rate: Rate) -> Self {
+        let mut playhead = Playhead::new();
+        playhead.restart(direction, length);
+        Self {
+            playhead,
+            rate,
+            clock: 0,
+            steps_in_loop: 0,
+            context: TriggerContext::default(),
+            last_note: None,
+        }
+    }
+
+    /// Start times of this lane's steps within the master step at `step_start`.
+    /// A rate change realigns the lane to the master clock.
+    fn starts_within(
+        &mut self,
+        rate: Rate,
+        step_start: Instant,
+        step_duration: Duration,
+    ) -> Vec<Instant> {
+        if rate != self.rate {
+            self.rate = rate;
+            self.clock = 0;
+        }
+
+        let mut starts = Vec::new();
+        while self.clock < rate.steps() {
+            let fraction = self.clock as f64 / rate.steps() as f64;
+            starts.push(step_start + step_duration.mul_f64(fraction));
+            self.clock += rate.per();
+        }
+        self.clock -= rate.steps();
+        starts
+    }
+
+    /// Evaluate the lane's current step and queue whatever it plays
+    fn play(
+        &mut self,
+        scheduler: &mut EventScheduler,
+        lane_step: &LaneStep,
+        key: &Key,
+        step_start: Instant,
+        step_duration: Duration,
+        rng: &mut fastrand::Rng,
+    ) {
+        let step = &lane_step.step;
+        let fired = step.fires(&self.context, rng);
+        self.context.previous_fired = fired;
+        if !fired {
+            return;
+        }
+
+        let note = key.note_for_degree(step.degree as i32);
+        let note_start = offset_by_steps(
+            step.nudged_start(step_start, step_duration),
+            step_duration,
+            lane_step.groove_offset as f64,
+        );
+        let gate = step.gate.clamp(Step::MIN_GATE, Step::MAX_GATE);
+        let held_until = note_start + step_duration.mul_f64(gate as f64);
+
+        // Ties (and slides onto the same pitch) hold the previous note
+        let holds = step.tie || (step.slide && self.last_note == Some(note));
+        if holds
+            && self
+                .last_note
+                .is_some_and(|prev| scheduler.extend_note_off(prev, held_until))
+        {
+            return;
+        }
+
+        let slide_from = self.last_note.filter(|_| step.slide);
+        for (i, scheduled) in step
+            .notes(step_duration, lane_step.velocity)
+            .iter()
+            .enumerate()
+        {
+            let overlap_until = note_start + SLIDE_OVERLAP;
+            let overlaps = i == 0
+                && slide_from.is_some_and(|prev| scheduler.extend_note_off(prev, overlap_until));
+            if overlaps {
+                scheduler.schedule_slide(note_start, note, scheduled);
+            } else {
+                scheduler.schedule_note(note_start, note, scheduled);
+            }
+        }
+        self.last_note = Some(note);
+    }
+
+    /// Position `advance` will move to, drawn from a copy of its generator
+    fn peek(&self, lane_step: &LaneStep, rng: &fastrand::Rng) -> usize {
+        let mut playhead = self.playhead;
+        playhead.advance(lane_step.direction, lane_step.length, &mut rng.clone())
+    }
+
+    /// Keep the note just played sounding until `next_start` if the next step
+    /// ties or slides into it. Steps are only scheduled a master step ahead, so
+    /// on slow lanes a short note would be released before the tie sees it.
+    fn hold_for(&self, scheduler: &mut EventScheduler, next: &Step, next_start: Instant) {
+        if !self.context.previous_fired || !next.active || !(next.tie || next.slide) {
+            return;
+        }
+        if let Some(note) = self.last_note {
+            scheduler.extend_note_off(note, next_start);
+        }
+    }
+
+    fn advance(&mut self, lane_step: &LaneStep, rng: &mut fastrand::Rng) {
+        self.steps_in_loop += 1;
+        if self.steps_in_loop >= lane_step.length {
+            self.steps_in_loop = 0;
+            self.context.loop_count += 1;
+        }
+        self.playhead
+            .advance(lane_step.direction, lane_step.length, rng);
+    }
+}
+
+fn lane_count(grid: &Grid) -> usize {
+    if grid.track_mode() {
+        grid.tracks().len()
+    } else {
+        1
+    }
+}
+
+pub(super) fn lanes_for(grid: &Grid) -> Vec<Lane> {
+    if grid.track_mode() {
+        grid.tracks()
+            .iter()
+            .map(|track| Lane::new(track.direction(), track.length(), track.rate()))
+            .collect()
+    } else {
+        vec![Lane::new(grid.direction(), grid.step_count(), Rate::NORMAL)]
+    }
+}
+
+/// Queue every lane step that starts within the master step at `step_start`
+pub(super) fn play_lanes(
+    lanes: &mut [Lane],
+    grid: &Grid,
+    scheduler: &mut EventScheduler,
+    key: &Key,
+    step_start: Instant,
+    step_duration: Duration,
+    rng: &mut fastrand::Rng,
+) {
+    for (index, lane) in lanes.iter_mut().enumerate() {
+        let rate = lane_rate(grid, index);
+        let lane_duration = rate.step_duration(step_duration);
+
+        for start in lane.starts_within(rate, step_start, step_duration) {
+            let position = lane.playhead.position();
+            let current = lane_step(grid, index, position);
+            let event = if grid.track_mode() {
+                PlaybackEvent::TrackStepAdvanced(index, position)
+            } else {
+                PlaybackEvent::StepAdvanced(position)
+            };
+            scheduler.schedule(start, event);
+
+            lane.play(scheduler, &current, key, start, lane_duration, rng);
+            let next = lane_step(grid, index, lane.peek(&current, rng));
+            lane.hold_for(scheduler, &next.step, start + lane_duration);
+            lane.advance(&current, rng);
+        }
+    }
+}
+
+/// Rates only apply per track; the single sequence-mode lane follows the master clock
+fn lane_rate(grid: &Grid, lane: usize) -> Rate {
+    match grid.track(lane) {
+        Some(track) if grid.track_mode() => track.rate(),
+        _ => Rate::NORMAL,
+    }
+}
+
+fn lane_step(grid: &Grid, lane: usize, position: usize) -> LaneStep {
+    let (step, length, direction) = match grid.track(lane) {
+        Some(track) if grid.track_mode() => {
+            (grid.step(position, lane), track.length(), track.direction())
+        }
+        _ => (grid.step_at(position), grid.step_count(), grid.direction()),
+    };
+
+    LaneStep {
+        step,
+        length,
+        direction,
+        groove_offset: grid.groove_offset(position),
+        velocity: grid.groove_velocity(position, 100),
+    }
+}
+
 impl Default for PlaybackEngine {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::midi::scale::Scale;
+
+    /// Drive one track the way the engine loop does, scheduling each master
+    /// step a step ahead, and return what it sends with the millisecond it's due
+    fn run_track(grid: &Grid, master_steps: u32) -> Vec<(u64, PlaybackEvent)> {
+        let step_duration = Duration::from_millis(100);
+        let key = Key::new(60, Scale::Chromatic);
+        let mut rng = fastrand::Rng::with_seed(1);
+        let mut scheduler = EventScheduler::new();
+        let mut lanes = lanes_for(grid);
+        let base = Instant::now() + step_duration;
+        let mut next_step = 0;
+        let mut sent = Vec::new();
+
+        for ms in 0..master_steps as u64 * 100 {
+            let now = base + Duration::from_millis(ms);
+            let step_start = base + step_duration * next_step;
+            if next_step < master_steps && now + step_duration >= step_start {
+                play_lanes(
+                    &mut lanes,
+                    grid,
+                    &mut scheduler,
+                    &key,
+                    step_start,
+                    step_duration,
+                    &mut rng,
+                );
+                next_step += 1;
+            }
+            sent.extend(scheduler.due(now).into_iter().map(|event| (ms, event)));
+        }
+        sent
+    }
+
+    fn note_events(sent: &[(u64, PlaybackEvent)]) -> Vec<(u64, bool)> {
+        sent.iter()
+            .filter_map(|(ms, event)| match event {
+                PlaybackEvent::NoteOn(..) => Some((*ms, true)),
+                PlaybackEvent::NoteOff(_) => Some((*ms, false)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_lane_rates_space_steps() {
+        let mut grid = Grid::new(4, 2);
+        grid.set_track_mode(true);
+        grid.track_mut(0).unwrap().set_rate(Rate::new(2, 1));
+        grid.track_mut(1).unwrap().set_rate(Rate::new(1, 2));
+        let note_ons: Vec<u64> = note_events(&run_track(&grid, 4))
+            .into_iter()
+            .filter_map(|(ms, on)| on.then_some(ms))
+            .collect();
+        assert_eq!(note_ons, vec![0, 0, 50, 100, 150, 200, 200, 250, 300, 350]);
+    }
+
+    #[test]
+    fn test_slow_lane_tie_holds_note() {
+        // A quarter-speed track: each of its steps lasts four master steps
+        let mut grid = Grid::new(2, 1);
+        grid.set_track_mode(true);
+        grid.track_mut(0).unwrap().set_rate(Rate::new(1, 4));
+        grid.step_mut(1, 0).unwrap().tie = true;
+
+        // The first note is held through the tied step, then retriggers
+        let notes = note_events(&run_track(&grid, 9));
+        assert_eq!(notes, vec![(0, true), (600, false), (800, true)]);
+    }
+}
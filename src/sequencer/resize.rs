@@ -0,0 +1,133 @@
+//! Grid resizing - changing a pattern's dimensions while keeping its content
+use super::{Grid, Step, Track};
+
+/// What fills the new cells when a grid grows. Shrinking always crops:
+/// cells outside the new size are dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResizeMode {
+    /// New cells start empty
+    #[default]
+    Pad,
+    /// New cells repeat the existing content (each track's loop in track mode)
+    Tile,
+}
+
+impl ResizeMode {
+    pub const ALL: [ResizeMode; 2] = [ResizeMode::Pad, ResizeMode::Tile];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ResizeMode::Pad => "Pad",
+            ResizeMode::Tile => "Tile",
+        }
+    }
+}
+
+impl Grid {
+    /// Change the grid's dimensions, keeping the cells that still fit.
+    /// Tracks covering the whole old width keep covering the whole grid, and
+    /// tempo changes past the end of the new loop are dropped.
+    pub fn resize(&mut self, width: usize, height: usize, mode: ResizeMode) {
+        let width = width.clamp(1, Self::MAX_WIDTH);
+        let height = height.clamp(1, Self::MAX_HEIGHT);
+        let (old_width, old_height) = (self.width, self.height);
+        let tile = mode == ResizeMode::Tile && old_width > 0 && old_height > 0;
+
+        // Horizontal repeat length of each old row
+        let periods: Vec<usize> = (0..old_height)
+            .map(|y| match self.tracks.get(y) {
+                Some(track) if self.track_mode => track.length().min(old_width),
+                _ => old_width,
+            })
+            .collect();
+
+        self.cells = (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| {
+                        if x < old_width && y < old_height {
+                            self.cells[y][x]
+                        } else if tile {
+                            let source_y = y % old_height;
+                            self.cells[source_y][x % periods[source_y]]
+                        } else {
+                            Step::default()
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        self.tracks = (0..height)
+            .map(|y| {
+                let source = match self.tracks.get(y) {
+                    Some(track) => Some(*track),
+                    None if tile => self.tracks.get(y % old_height).copied(),
+                    None => None,
+                };
+                let Some(mut track) = source else {
+                    return Track::new(width);
+                };
+                if track.length() >= old_width {
+                    track.set_length(width);
+                } else {
+                    track.set_length(track.length().min(width));
+                }
+                track
+            })
+            .collect();
+
+        self.width = width;
+        self.height = height;
+
+        let loop_length = self.loop_length();
+        self.tempo_changes.retain(|(step, _)| *step < loop_length);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequencer::fixtures::row;
+
+    #[test]
+    fn test_resize_pads_and_crops() {
+        let mut grid = Grid::new(2, 2);
+        grid.set(1, 0, false);
+        grid.resize(4, 3, ResizeMode::Pad);
+        assert_eq!((grid.width(), grid.height()), (4, 3));
+        assert_eq!(row(&grid, 0), "x...");
+        assert_eq!(row(&grid, 2), "....");
+        assert_eq!(grid.tracks().len(), 3);
+        assert_eq!(grid.track(0).unwrap().length(), 4);
+
+        grid.resize(1, 1, ResizeMode::Pad);
+        assert_eq!(row(&grid, 0), "x");
+        assert_eq!(grid.tracks().len(), 1);
+    }
+
+    #[test]
+    fn test_resize_tiles_track_loops() {
+        let mut grid = Grid::new(4, 1);
+        grid.set_track_mode(true);
+        grid.track_mut(0).unwrap().set_length(3);
+        grid.set(1, 0, false);
+        grid.set(3, 0, false);
+
+        grid.resize(8, 2, ResizeMode::Tile);
+        assert_eq!(row(&grid, 0), "x.x..xx.");
+        assert_eq!(row(&grid, 1), "x.xx.xx.");
+        assert_eq!(grid.track(1).unwrap().length(), 3);
+    }
+
+    #[test]
+    fn test_resize_drops_tempo_changes_past_the_loop() {
+        use crate::sequencer::TempoChange;
+
+        let mut grid = Grid::new(4, 4);
+        grid.set_tempo_change(2, Some(TempoChange::new(90.0)));
+        grid.set_tempo_change(12, Some(TempoChange::new(140.0)));
+        grid.resize(4, 2, ResizeMode::Pad);
+        assert_eq!(grid.tempo_changes().len(), 1);
+    }
+}
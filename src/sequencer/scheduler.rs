@@ -0,0 +1,328 @@
+//! Event scheduling - places note events at precise times within a step
+use super::playback::PlaybackEvent;
+use super::Step;
+use std::time::{Duration, Instant};
+
+/// A note a step plays, timed relative to the start of the step
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScheduledNote {
+    pub offset: Duration,
+    pub length: Duration,
+    pub velocity: u8,
+}
+
+/// How velocity changes across a step's ratchets
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VelocityRamp {
+    #[default]
+    Flat,
+    Up,
+    Down,
+}
+
+impl VelocityRamp {
+    pub const ALL: [VelocityRamp; 3] = [VelocityRamp::Flat, VelocityRamp::Up, VelocityRamp::Down];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            VelocityRamp::Flat => "Flat",
+            VelocityRamp::Up => "Ramp up",
+            VelocityRamp::Down => "Ramp down",
+        }
+    }
+
+    fn scale(&self, velocity: u8, index: usize, count: usize) -> u8 {
+        let factor = match self {
+            VelocityRamp::Flat => 1.0,
+            VelocityRamp::Up => (index + 1) as f32 / count as f32,
+            VelocityRamp::Down => (count - index) as f32 / count as f32,
+        };
+        ((velocity as f32 * factor).round() as u8).max(1)
+    }
+}
+
+impl Step {
+    /// When this step actually sounds, after applying its microtiming nudge
+    pub fn nudged_start(&self, step_start: Instant, step_duration: Duration) -> Instant {
+        let nudge = self.nudge.clamp(-Step::MAX_NUDGE, Step::MAX_NUDGE);
+        let steps = nudge as f64 / Step::NUDGE_TICKS_PER_STEP as f64;
+        offset_by_steps(step_start, step_duration, steps)
+    }
+
+    /// Notes this step plays, splitting it into evenly spaced ratchets.
+    /// Ratchets apply the gate to their own sub-step and never overlap.
+    pub fn notes(&self, step_duration: Duration, velocity: u8) -> Vec<ScheduledNote> {
+        let count = self.ratchet.clamp(1, 8) as usize;
+        let spacing = step_duration / count as u32;
+        let gate = self.gate.clamp(Step::MIN_GATE, Step::MAX_GATE);
+        let length = if count > 1 {
+            spacing.mul_f64(gate.min(1.0) as f64)
+        } else {
+            step_duration.mul_f64(gate as f64)
+        };
+
+        (0..count)
+            .map(|i| ScheduledNote {
+                offset: spacing * i as u32,
+                length,
+                velocity: self.velocity_ramp.scale(velocity, i, count),
+            })
+            .collect()
+    }
+}
+
+/// Shift an instant by a signed number of (possibly fractional) steps
+pub fn offset_by_steps(start: Instant, step_duration: Duration, steps: f64) -> Instant {
+    let offset = step_duration.mul_f64(steps.abs());
+    if steps < 0.0 {
+        start - offset
+    } else {
+        start + offset
+    }
+}
+
+/// Time-ordered queue of future playback events, drained by the engine loop
+#[derive(Debug, Default)]
+pub struct EventScheduler {
+    queue: Vec<(Instant, u64, PlaybackEvent)>,
+    next_sequence: u64,
+}
+
+impl EventScheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue an event; events at the same instant keep insertion order
+    pub fn schedule(&mut self, at: Instant, event: PlaybackEvent) {
+        self.queue.push((at, self.next_sequence, event));
+        self.next_sequence += 1;
+    }
+
+    /// Queue a note-on/note-off pair for `note`. An earlier note of the same
+    /// pitch still held by a long gate is released first, then retriggered.
+    pub fn schedule_note(&mut self, start: Instant, note: u8, scheduled: &ScheduledNote) {
+        let on = start + scheduled.offset;
+        self.release_by(note, on);
+        self.schedule(on, PlaybackEvent::NoteOn(note, scheduled.velocity));
+        self.schedule(on + scheduled.length, PlaybackEvent::NoteOff(note));
+    }
+
+    /// Queue a note that glides from the previous one (`SlideTo` instead of `NoteOn`)
+    pub fn schedule_slide(&mut self, start: Instant, note: u8, scheduled: &ScheduledNote) {
+        let on = start + scheduled.offset;
+        self.schedule(on, PlaybackEvent::SlideTo(note, scheduled.velocity));
+        self.schedule(on + scheduled.length, PlaybackEvent::NoteOff(note));
+    }
+
+    /// Bring every pending note-off for `note` later than `at` forward to `at`.
+    /// Queued earlier, they still sort before a note-on at the same instant.
+    fn release_by(&mut self, note: u8, at: Instant) {
+        for (time, _, event) in &mut self.queue {
+            if matches!(event, PlaybackEvent::NoteOff(n) if *n == note) && *time > at {
+                *time = at;
+            }
+        }
+    }
+
+    /// Push the latest pending note-off for `note` back to at least `until`.
+    /// Returns false if the note has already been released and can't be held.
+    pub fn extend_note_off(&mut self, note: u8, until: Instant) -> bool {
+        let pending = self
+            .queue
+            .iter_mut()
+            .filter(|(_, _, event)| matches!(event, PlaybackEvent::NoteOff(n) if *n == note))
+            .max_by_key(|&&mut (time, sequence, _)| (time, sequence));
+
+        match pending {
+            Some(entry) => {
+                entry.0 = entry.0.max(until);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove and return every event due at or before `now`, in time order
+    pub fn due(&mut self, now: Instant) -> Vec<PlaybackEvent> {
+        let mut due: Vec<_> = Vec::new();
+        let mut i = 0;
+        while i < self.queue.len() {
+            if self.queue[i].0 <= now {
+                due.push(self.queue.swap_remove(i));
+            } else {
+                i += 1;
+            }
+        }
+        due.sort_by_key(|&(at, sequence, _)| (at, sequence));
+        due.into_iter().map(|(_, _, event)| event).collect()
+    }
+
+    /// Remove and return every pending event with its time, in time order
+    pub fn drain(&mut self) -> Vec<(Instant, PlaybackEvent)> {
+        let mut pending = std::mem::take(&mut self.queue);
+        pending.sort_by_key(|&(at, sequence, _)| (at, sequence));
+        pending
+            .into_iter()
+            .map(|(at, _, event)| (at, event))
+            .collect()
+    }
+
+    /// Drop everything except pending note-offs (including click releases),
+    /// returned so no note hangs. Slide overlaps end in plain note-offs.
+    pub fn flush_note_offs(&mut self) -> Vec<PlaybackEvent> {
+        let mut pending = std::mem::take(&mut self.queue);
+        pending.sort_by_key(|&(at, sequence, _)| (at, sequence));
+        pending
+            .into_iter()
+            .filter_map(|(_, _, event)| match event {
+                PlaybackEvent::NoteOff(_) | PlaybackEvent::ClickOff => Some(event),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ratchets_are_evenly_spaced() {
+        let step = Step {
+            ratchet: 4,
+            velocity_ramp: VelocityRamp::Up,
+            ..Step::new(true)
+        };
+        let notes = step.notes(Duration::from_millis(200), 100);
+        let offsets: Vec<u64> = notes.iter().map(|n| n.offset.as_millis() as u64).collect();
+        let velocities: Vec<u8> = notes.iter().map(|n| n.velocity).collect();
+        assert_eq!(offsets, vec![0, 50, 100, 150]);
+        assert_eq!(velocities, vec![25, 50, 75, 100]);
+        assert!(notes.iter().all(|n| n.length == Duration::from_millis(25)));
+    }
+
+    #[test]
+    fn test_gate_length_in_steps() {
+        let step = Step {
+            gate: 2.0,
+            ..Step::new(true)
+        };
+        let notes = step.notes(Duration::from_millis(100), 100);
+        assert_eq!(notes[0].length, Duration::from_millis(200));
+    }
+
+    #[test]
+    fn test_nudge_shifts_step_start() {
+        let start = Instant::now() + Duration::from_secs(1);
+        let step_duration = Duration::from_millis(96);
+        let late = Step {
+            nudge: 24,
+            ..Step::new(true)
+        };
+        let early = Step {
+            nudge: -Step::MAX_NUDGE,
+            ..Step::new(true)
+        };
+        assert_eq!(
+            late.nudged_start(start, step_duration),
+            start + Duration::from_millis(24)
+        );
+        assert_eq!(
+            early.nudged_start(start, step_duration),
+            start - Duration::from_millis(48)
+        );
+    }
+
+    #[test]
+    fn test_extend_note_off_holds_note() {
+        let start = Instant::now();
+        let mut scheduler = EventScheduler::new();
+        let note = Step::new(true).notes(Duration::from_millis(100), 100)[0];
+        scheduler.schedule_note(start, 60, &note);
+
+        assert!(scheduler.extend_note_off(60, start + Duration::from_millis(300)));
+        assert!(scheduler.extend_note_off(60, start));
+        assert!(!scheduler.extend_note_off(61, start));
+        let due = scheduler.due(start + Duration::from_millis(200));
+        assert!(matches!(due[..], [PlaybackEvent::NoteOn(60, 100)]));
+    }
+
+    #[test]
+    fn test_long_gates_retrigger_same_pitch() {
+        let start = Instant::now();
+        let step_duration = Duration::from_millis(100);
+        let mut scheduler = EventScheduler::new();
+        let step = Step {
+            gate: 4.0,
+            ..Step::new(true)
+        };
+        for i in 0..3 {
+            let note = step.notes(step_duration, 100)[0];
+            scheduler.schedule_note(start + step_duration * i, 60, &note);
+        }
+
+        // Each note releases as the next one starts, and only the last rings on
+        let due = scheduler.due(start + Duration::from_millis(250));
+        assert!(matches!(
+            due[..],
+            [
+                PlaybackEvent::NoteOn(60, _),
+                PlaybackEvent::NoteOff(60),
+                PlaybackEvent::NoteOn(60, _),
+                PlaybackEvent::NoteOff(60),
+                PlaybackEvent::NoteOn(60, _),
+            ]
+        ));
+        assert!(scheduler.due(start + Duration::from_millis(599)).is_empty());
+        let due = scheduler.due(start + Duration::from_millis(600));
+        assert!(matches!(due[..], [PlaybackEvent::NoteOff(60)]));
+        assert!(scheduler.is_empty());
+    }
+
+    #[test]
+    fn test_scheduler_orders_due_events() {
+        let start = Instant::now();
+        let mut scheduler = EventScheduler::new();
+        let step = Step {
+            ratchet: 2,
+            ..Step::new(true)
+        };
+        for note in step.notes(Duration::from_millis(100), 100) {
+            scheduler.schedule_note(start, 60, &note);
+        }
+
+        let due = scheduler.due(start + Duration::from_millis(60));
+        assert_eq!(due.len(), 3);
+        assert!(matches!(due[0], PlaybackEvent::NoteOn(60, 100)));
+        assert!(matches!(due[1], PlaybackEvent::NoteOff(60)));
+        assert!(matches!(due[2], PlaybackEvent::NoteOn(60, 100)));
+
+        let flushed = scheduler.flush_note_offs();
+        assert!(matches!(flushed[..], [PlaybackEvent::NoteOff(60)]));
+        assert!(scheduler.is_empty());
+    }
+
+    #[test]
+    fn test_flush_keeps_click_release() {
+        let start = Instant::now();
+        let mut scheduler = EventScheduler::new();
+        scheduler.schedule(start, PlaybackEvent::Click(true));
+        scheduler.schedule(start + Duration::from_millis(30), PlaybackEvent::ClickOff);
+        scheduler.schedule(start, PlaybackEvent::SlideTo(62, 100));
+        scheduler.schedule(
+            start + Duration::from_millis(10),
+            PlaybackEvent::NoteOff(60),
+        );
+
+        let flushed = scheduler.flush_note_offs();
+        assert!(matches!(
+            flushed[..],
+            [PlaybackEvent::NoteOff(60), PlaybackEvent::ClickOff]
+        ));
+    }
+}
@@ -0,0 +1,426 @@
+//! Song mode - an arrangement of bank patterns played in order
+
+use super::{PatternBank, TempoChange, TempoRamp};
+use crate::midi::smf::SmfTempo;
+
+/// One section of the arrangement: a bank slot played `repeats` times,
+/// optionally changing tempo as it starts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SongEntry {
+    pub pattern: usize,
+    pub repeats: usize,
+    pub tempo: Option<TempoChange>,
+}
+
+impl SongEntry {
+    pub fn new(pattern: usize, repeats: usize) -> Self {
+        Self {
+            pattern,
+            repeats: repeats.max(1),
+            tempo: None,
+        }
+    }
+
+    pub fn with_tempo(mut self, tempo: TempoChange) -> Self {
+        self.tempo = Some(tempo);
+        self
+    }
+}
+
+/// The editable part of a song - its entries and loop - as recorded by the
+/// undo history, leaving out the playback position
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arrangement {
+    pub entries: Vec<SongEntry>,
+    pub loop_range: Option<(usize, usize)>,
+}
+
+/// Where playback is within the arrangement
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SongPosition {
+    pub entry: usize,
+    pub repeat: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Song {
+    entries: Vec<SongEntry>,
+    enabled: bool,
+    loop_range: Option<(usize, usize)>,
+    position: SongPosition,
+    pending_jump: Option<usize>,
+}
+
+impl Song {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn entries(&self) -> &[SongEntry] {
+        &self.entries
+    }
+
+    pub fn entry_mut(&mut self, index: usize) -> Option<&mut SongEntry> {
+        self.entries.get_mut(index)
+    }
+
+    pub fn arrangement(&self) -> Arrangement {
+        Arrangement {
+            entries: self.entries.clone(),
+            loop_range: self.loop_range,
+        }
+    }
+
+    /// Replace the entries and loop, rewinding if the position no longer exists
+    pub fn set_arrangement(&mut self, arrangement: Arrangement) {
+        self.entries = arrangement.entries;
+        self.loop_range = None;
+        if let Some((start, end)) = arrangement.loop_range {
+            self.set_loop(start, end);
+        }
+        if self
+            .pending_jump
+            .is_some_and(|jump| jump >= self.entries.len())
+        {
+            self.pending_jump = None;
+        }
+        if self.position.entry >= self.entries.len() {
+            self.position = SongPosition::default();
+        }
+    }
+
+    pub fn push(&mut self, entry: SongEntry) {
+        self.entries.push(entry);
+    }
+
+    pub fn insert(&mut self, index: usize, entry: SongEntry) {
+        self.entries.insert(index.min(self.entries.len()), entry);
+    }
+
+    /// Remove an entry, shifting the position, pending jump and loop so they
+    /// keep pointing at the same entries
+    pub fn remove(&mut self, index: usize) {
+        if index >= self.entries.len() {
+            return;
+        }
+        self.entries.remove(index);
+
+        self.pending_jump = match self.pending_jump {
+            Some(jump) if jump == index => None,
+            Some(jump) if jump > index => Some(jump - 1),
+            jump => jump,
+        };
+
+        if self.position.entry > index {
+            self.position.entry -= 1;
+        } else if self.position.entry == index {
+            // The following entry takes its place, from its first repeat
+            self.position.repeat = 0;
+        }
+        if self.position.entry >= self.entries.len() {
+            self.position = SongPosition::default();
+        }
+
+        self.loop_range = self.loop_range.and_then(|(start, end)| {
+            if start == index && end == index {
+                return None;
+            }
+            let start = if start > index { start - 1 } else { start };
+            let end = if end >= index { end - 1 } else { end };
+            (start <= end && end < self.entries.len()).then_some((start, end))
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Song mode only drives playback when enabled and non-empty
+    pub fn is_active(&self) -> bool {
+        self.enabled && !self.entries.is_empty()
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Loop between two entries (inclusive) instead of ending the song
+    pub fn set_loop(&mut self, start: usize, end: usize) {
+        if start <= end && end < self.entries.len() {
+            self.loop_range = Some((start, end));
+        }
+    }
+
+    pub fn clear_loop(&mut self) {
+        self.loop_range = None;
+    }
+
+    pub fn loop_range(&self) -> Option<(usize, usize)> {
+        self.loop_range
+    }
+
+    pub fn position(&self) -> SongPosition {
+        self.position
+    }
+
+    /// Tempo change of the current entry, due when its first repeat starts
+    pub fn entry_tempo(&self) -> Option<TempoChange> {
+        if self.position.repeat != 0 {
+            return None;
+        }
+        self.entries.get(self.position.entry).and_then(|e| e.tempo)
+    }
+
+    /// Pattern for the current position, if the song has any entries
+    pub fn current_pattern(&self) -> Option<usize> {
+        self.entries.get(self.position.entry).map(|e| e.pattern)
+    }
+
+    /// Jump to an entry at the next loop boundary, keeping playback in time
+    pub fn jump_to(&mut self, entry: usize) {
+        if entry < self.entries.len() {
+            self.pending_jump = Some(entry);
+        }
+    }
+
+    /// Move to an entry immediately, for use while stopped
+    pub fn seek(&mut self, entry: usize) {
+        if entry < self.entries.len() {
+            self.position = SongPosition { entry, repeat: 0 };
+            self.pending_jump = None;
+        }
+    }
+
+    pub fn pending_jump(&self) -> Option<usize> {
+        self.pending_jump
+    }
+
+    /// Move back to the first entry
+    pub fn reset(&mut self) {
+        self.position = SongPosition::default();
+        self.pending_jump = None;
+    }
+
+    /// Tempo meta events for one pass through the arrangement starting at
+    /// `bpm`, with ramps sampled once per step. Ticks are `TICKS_PER_QUARTER`
+    /// per quarter note; grid tempo changes win over an entry's, as in playback.
+    pub fn tempo_map(&self, bank: &PatternBank, bpm: f32) -> Vec<SmfTempo> {
+        let mut tempos = vec![SmfTempo { tick: 0, bpm }];
+        let mut tick = 0u64;
+        let mut current = bpm;
+        let mut ramp: Option<TempoRamp> = None;
+
+        for entry in &self.entries {
+            let Some(grid) = bank.pattern(entry.pattern) else {
+                continue;
+            };
+            let step_ticks = grid.resolution().step_ticks();
+            for repeat in 0..entry.repeats {
+                for step in 0..grid.loop_length() {
+                    let entry_tempo = entry.tempo.filter(|_| repeat == 0 && step == 0);
+                    if let Some(change) = grid.tempo_change_at(step).or(entry_tempo) {
+                        let started = TempoRamp::new(current, change, grid.time_signature());
+                        // A new change cuts short whatever ramp was in progress
+                        tempos.retain(|t| t.tick < tick);
+                        tempos.extend(started.smf_tempos(tick, step_ticks));
+                        ramp = Some(started);
+                    }
+                    if let Some(active) = &mut ramp {
+                        active.advance(step_ticks);
+                        current = active.bpm();
+                        if active.is_finished() {
+                            ramp = None;
+                        }
+                    }
+                    tick += step_ticks as u64;
+                }
+            }
+        }
+
+        tempos
+    }
+
+    /// Called at the end of each pattern loop. Returns the new position, or
+    /// `None` once the song has finished (the position rewinds to the start).
+    pub fn advance(&mut self) -> Option<SongPosition> {
+        if let Some(entry) = self.pending_jump.take() {
+            self.position = SongPosition { entry, repeat: 0 };
+            return Some(self.position);
+        }
+
+        let entry = self.entries.get(self.position.entry)?;
+        if self.position.repeat + 1 < entry.repeats {
+            self.position.repeat += 1;
+            return Some(self.position);
+        }
+
+        let next = match self.loop_range {
+            Some((start, end)) if self.position.entry == end => start,
+            _ => self.position.entry + 1,
+        };
+
+        if next < self.entries.len() {
+            self.position = SongPosition {
+                entry: next,
+                repeat: 0,
+            };
+            Some(self.position)
+        } else {
+            self.reset();
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn song() -> Song {
+        let mut song = Song::new();
+        song.push(SongEntry::new(0, 2));
+        song.push(SongEntry::new(3, 1));
+        song.push(SongEntry::new(1, 1));
+        song
+    }
+
+    #[test]
+    fn test_song_walks_entries_and_repeats() {
+        let mut song = song();
+        assert_eq!(song.current_pattern(), Some(0));
+        assert_eq!(
+            song.advance(),
+            Some(SongPosition {
+                entry: 0,
+                repeat: 1
+            })
+        );
+        assert_eq!(
+            song.advance(),
+            Some(SongPosition {
+                entry: 1,
+                repeat: 0
+            })
+        );
+        assert_eq!(song.current_pattern(), Some(3));
+        assert_eq!(
+            song.advance(),
+            Some(SongPosition {
+                entry: 2,
+                repeat: 0
+            })
+        );
+        assert_eq!(song.advance(), None);
+        assert_eq!(song.position(), SongPosition::default());
+    }
+
+    #[test]
+    fn test_song_loop_and_jump() {
+        let mut song = song();
+        song.set_loop(1, 2);
+        song.jump_to(1);
+        assert_eq!(
+            song.advance(),
+            Some(SongPosition {
+                entry: 1,
+                repeat: 0
+            })
+        );
+        assert_eq!(
+            song.advance(),
+            Some(SongPosition {
+                entry: 2,
+                repeat: 0
+            })
+        );
+        assert_eq!(
+            song.advance(),
+            Some(SongPosition {
+                entry: 1,
+                repeat: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_tempo_map_follows_entries_and_patterns() {
+        use crate::sequencer::meter::TICKS_PER_QUARTER;
+        use crate::sequencer::TempoCurve;
+
+        let mut bank = PatternBank::new(2, 4, 1);
+        bank.pattern_mut(1)
+            .unwrap()
+            .set_tempo_change(2, Some(TempoChange::new(90.0)));
+        let mut song = Song::new();
+        song.push(SongEntry::new(0, 1));
+        song.push(SongEntry::new(0, 1).with_tempo(TempoChange::new(140.0)));
+        song.push(SongEntry::new(1, 1).with_tempo(TempoChange::ramp(160.0, 1, TempoCurve::Linear)));
+
+        let tempos = song.tempo_map(&bank, 120.0);
+        // Each 4-step pattern of sixteenths lasts a quarter note
+        let quarter = TICKS_PER_QUARTER as u64;
+        assert_eq!(
+            tempos.first(),
+            Some(&SmfTempo {
+                tick: 0,
+                bpm: 120.0
+            })
+        );
+        assert!(tempos.contains(&SmfTempo {
+            tick: quarter,
+            bpm: 140.0
+        }));
+        // The one-bar ramp is cut short by pattern 1's change on its third step
+        let last = tempos.last().unwrap();
+        assert_eq!((last.tick, last.bpm), (2 * quarter + quarter / 2, 90.0));
+        assert!(tempos.iter().all(|t| t.bpm <= 160.0));
+    }
+
+    #[test]
+    fn test_remove_shifts_jump_and_loop() {
+        let mut song = song();
+        song.set_loop(0, 1);
+        song.jump_to(2);
+        song.remove(2);
+        assert_eq!(song.pending_jump(), None);
+        assert_eq!(
+            song.advance(),
+            Some(SongPosition {
+                entry: 0,
+                repeat: 1
+            })
+        );
+        assert_eq!(
+            song.advance(),
+            Some(SongPosition {
+                entry: 1,
+                repeat: 0
+            })
+        );
+        assert_eq!(
+            song.advance(),
+            Some(SongPosition {
+                entry: 0,
+                repeat: 0
+            })
+        );
+
+        let mut longer = self::song();
+        longer.push(SongEntry::new(2, 1));
+        longer.set_loop(2, 3);
+        longer.seek(3);
+        longer.jump_to(2);
+        longer.remove(0);
+        assert_eq!(longer.loop_range(), Some((1, 2)));
+        assert_eq!(longer.position().entry, 2);
+        assert_eq!(longer.pending_jump(), Some(1));
+    }
+}
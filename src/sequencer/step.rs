@@ -0,0 +1,57 @@
+//! Per-step data stored in each grid cell
+use super::condition::Condition;
+use super::scheduler::VelocityRamp;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Step {
+    pub active: bool,
+    /// Scale degree relative to the key root, resolved to a note at playback
+    pub degree: i8,
+    /// Chance of firing, 0-100%
+    pub probability: u8,
+    pub condition: Condition,
+    /// Number of evenly spaced retriggers within the step, 1-8
+    pub ratchet: u8,
+    pub velocity_ramp: VelocityRamp,
+    /// Note length in steps: 0.5 is half a step, 2.0 holds across two steps
+    pub gate: f32,
+    /// Hold the previous note through this step instead of retriggering
+    pub tie: bool,
+    /// Play legato from the previous note: overlapping MIDI notes, pitch glide on the synth
+    pub slide: bool,
+    /// Microtiming offset in ticks of `NUDGE_TICKS_PER_STEP`; negative plays early.
+    /// Kept in clipboard text and written into exported MIDI files.
+    pub nudge: i8,
+}
+
+impl Step {
+    pub const MIN_GATE: f32 = 0.05;
+    pub const MAX_GATE: f32 = 16.0;
+    /// Resolution of `nudge`; a nudge of ±48 ticks is half a step either way
+    pub const NUDGE_TICKS_PER_STEP: i8 = 96;
+    pub const MAX_NUDGE: i8 = Self::NUDGE_TICKS_PER_STEP / 2;
+
+    pub fn new(active: bool) -> Self {
+        Self {
+            active,
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for Step {
+    fn default() -> Self {
+        Self {
+            active: false,
+            degree: 0,
+            probability: 100,
+            condition: Condition::Always,
+            ratchet: 1,
+            velocity_ramp: VelocityRamp::Flat,
+            gate: 0.5,
+            tie: false,
+            slide: false,
+            nudge: 0,
+        }
+    }
+}
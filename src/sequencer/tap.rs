@@ -0,0 +1,116 @@
+//! Tap tempo - estimates BPM from the spacing of recent taps
+use super::Sequencer;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Taps whose interval strays this far from the median are ignored
+const OUTLIER_TOLERANCE: f64 = 0.25;
+
+/// A pause this long starts a new tap sequence: a beat at the slowest
+/// tempo, plus the same slack allowed for outliers
+const TAP_TIMEOUT: Duration = Duration::from_millis(
+    (60_000.0 / Sequencer::MIN_BPM as f64 * (1.0 + OUTLIER_TOLERANCE)) as u64,
+);
+
+#[derive(Debug, Clone, Default)]
+pub struct TapTempo {
+    taps: VecDeque<Instant>,
+}
+
+impl TapTempo {
+    /// Number of taps averaged
+    pub const MAX_TAPS: usize = 8;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a tap and return the tempo estimate once there are two taps
+    pub fn tap(&mut self, at: Instant) -> Option<f32> {
+        if self
+            .taps
+            .back()
+            .is_some_and(|&last| at.saturating_duration_since(last) > TAP_TIMEOUT)
+        {
+            self.taps.clear();
+        }
+
+        self.taps.push_back(at);
+        if self.taps.len() > Self::MAX_TAPS {
+            self.taps.pop_front();
+        }
+
+        self.bpm()
+    }
+
+    /// Average of the recent tap intervals as BPM, ignoring outliers
+    pub fn bpm(&self) -> Option<f32> {
+        let intervals: Vec<f64> = self
+            .taps
+            .iter()
+            .zip(self.taps.iter().skip(1))
+            .map(|(a, b)| b.saturating_duration_since(*a).as_secs_f64())
+            .filter(|&interval| interval > 0.0)
+            .collect();
+        if intervals.is_empty() {
+            return None;
+        }
+
+        let mut sorted = intervals.clone();
+        sorted.sort_by(f64::total_cmp);
+        let median = sorted[sorted.len() / 2];
+
+        let kept: Vec<f64> = intervals
+            .into_iter()
+            .filter(|interval| (interval - median).abs() <= median * OUTLIER_TOLERANCE)
+            .collect();
+        let average = kept.iter().sum::<f64>() / kept.len() as f64;
+        Some((60.0 / average) as f32)
+    }
+
+    pub fn tap_count(&self) -> usize {
+        self.taps.len()
+    }
+
+    pub fn reset(&mut self) {
+        self.taps.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tap_tempo_rejects_outliers() {
+        let mut tap_tempo = TapTempo::new();
+        let start = Instant::now();
+        let times = [0, 500, 1000, 1500, 1750, 2250, 2750];
+        let mut bpm = None;
+        for ms in times {
+            bpm = tap_tempo.tap(start + Duration::from_millis(ms));
+        }
+
+        // The late 250ms tap is ignored, leaving 500ms intervals
+        assert!((bpm.unwrap() - 120.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_tap_tempo_resets_after_pause() {
+        let mut tap_tempo = TapTempo::new();
+        let start = Instant::now();
+        assert_eq!(tap_tempo.tap(start), None);
+        assert!(tap_tempo.tap(start + Duration::from_millis(400)).is_some());
+        assert_eq!(tap_tempo.tap(start + Duration::from_secs(5)), None);
+        assert_eq!(tap_tempo.tap_count(), 1);
+    }
+
+    #[test]
+    fn test_tap_tempo_reaches_min_bpm() {
+        let mut tap_tempo = TapTempo::new();
+        let start = Instant::now();
+        tap_tempo.tap(start);
+        let bpm = tap_tempo.tap(start + Duration::from_secs(3)).unwrap();
+        assert!((bpm - Sequencer::MIN_BPM).abs() < 0.01);
+    }
+}
@@ -0,0 +1,210 @@
+//! Tempo automation - tempo changes, optionally ramped over a number of bars
+use super::meter::{TimeSignature, TICKS_PER_QUARTER};
+use super::Sequencer;
+use crate::midi::smf::SmfTempo;
+use std::time::Duration;
+
+/// Shape of a tempo ramp
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TempoCurve {
+    #[default]
+    Linear,
+    /// Changes by the same ratio every bar, which sounds even to the ear
+    Exponential,
+}
+
+impl TempoCurve {
+    pub const ALL: [TempoCurve; 2] = [TempoCurve::Linear, TempoCurve::Exponential];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TempoCurve::Linear => "Linear",
+            TempoCurve::Exponential => "Curved",
+        }
+    }
+
+    /// Tempo `progress` (0-1) of the way from `from` to `to`
+    pub fn interpolate(&self, from: f32, to: f32, progress: f32) -> f32 {
+        let progress = progress.clamp(0.0, 1.0);
+        match self {
+            TempoCurve::Linear => from + (to - from) * progress,
+            TempoCurve::Exponential => from * (to / from).powf(progress),
+        }
+    }
+}
+
+/// Move to `bpm`, at once when `bars` is 0 or ramping over that many bars
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TempoChange {
+    pub bpm: f32,
+    pub bars: u32,
+    pub curve: TempoCurve,
+}
+
+impl TempoChange {
+    pub const MAX_BARS: u32 = 256;
+
+    pub fn new(bpm: f32) -> Self {
+        Self::ramp(bpm, 0, TempoCurve::Linear)
+    }
+
+    pub fn ramp(bpm: f32, bars: u32, curve: TempoCurve) -> Self {
+        Self {
+            bpm: clamp_bpm(bpm),
+            bars: bars.min(Self::MAX_BARS),
+            curve,
+        }
+    }
+}
+
+/// Limit a tempo to the sequencer's range; NaN becomes the minimum
+pub(crate) fn clamp_bpm(bpm: f32) -> f32 {
+    if bpm.is_nan() {
+        return Sequencer::MIN_BPM;
+    }
+    bpm.clamp(Sequencer::MIN_BPM, Sequencer::MAX_BPM)
+}
+
+/// A tempo change in progress, tracked in musical ticks so step times can
+/// be integrated across the curve instead of sampled once per step
+#[derive(Debug, Clone)]
+pub struct TempoRamp {
+    from: f32,
+    change: TempoChange,
+    length_ticks: u32,
+    elapsed_ticks: u32,
+}
+
+impl TempoRamp {
+    /// The fields of `change` are public, so its tempo and length are
+    /// limited again here before anything divides by them
+    pub fn new(from: f32, change: TempoChange, signature: TimeSignature) -> Self {
+        let change = TempoChange::ramp(change.bpm, change.bars, change.curve);
+        Self {
+            from: clamp_bpm(from),
+            change,
+            length_ticks: change.bars.saturating_mul(signature.bar_ticks()),
+            elapsed_ticks: 0,
+        }
+    }
+
+    /// Tempo at the current point of the ramp
+    pub fn bpm(&self) -> f32 {
+        self.bpm_at(self.elapsed_ticks as f64)
+    }
+
+    pub fn target(&self) -> f32 {
+        self.change.bpm
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.elapsed_ticks >= self.length_ticks
+    }
+
+    /// How long the next `ticks` ticks take, then move past them
+    pub fn advance(&mut self, ticks: u32) -> Duration {
+        let seconds = self.seconds_between(self.elapsed_ticks, ticks);
+        self.elapsed_ticks += ticks;
+        Duration::from_secs_f64(seconds)
+    }
+
+    /// The whole ramp as tempo meta events every `interval` ticks from
+    /// `start_tick`, each holding the average tempo of its interval
+    pub fn smf_tempos(&self, start_tick: u64, interval: u32) -> Vec<SmfTempo> {
+        let interval = interval.max(1);
+        let mut tempos = Vec::new();
+        let mut tick = 0;
+        while tick < self.length_ticks {
+            let ticks = interval.min(self.length_ticks - tick);
+            let quarters = ticks as f64 / TICKS_PER_QUARTER as f64;
+            let bpm = 60.0 * quarters / self.seconds_between(tick, ticks);
+            tempos.push(SmfTempo {
+                tick: start_tick + tick as u64,
+                bpm: bpm as f32,
+            });
+            tick += ticks;
+        }
+        tempos.push(SmfTempo {
+            tick: start_tick + self.length_ticks as u64,
+            bpm: self.change.bpm,
+        });
+        tempos
+    }
+
+    fn bpm_at(&self, tick: f64) -> f32 {
+        if self.length_ticks == 0 {
+            return self.change.bpm;
+        }
+        let progress = (tick / self.length_ticks as f64) as f32;
+        self.change
+            .curve
+            .interpolate(self.from, self.change.bpm, progress)
+    }
+
+    /// Midpoint-rule integral of the tick length across the curve
+    fn seconds_between(&self, start: u32, ticks: u32) -> f64 {
+        (start..start + ticks)
+            .map(|tick| {
+                let bpm = self.bpm_at(tick as f64 + 0.5) as f64;
+                60.0 / (bpm * TICKS_PER_QUARTER as f64)
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linear_ramp_integrates_step_times() {
+        let change = TempoChange::ramp(240.0, 1, TempoCurve::Linear);
+        let mut ramp = TempoRamp::new(120.0, change, TimeSignature::COMMON);
+
+        // 16 sixteenths over one bar of 4/4
+        let total: Duration = (0..16).map(|_| ramp.advance(24)).sum();
+        let expected = 2.0 * std::f64::consts::LN_2;
+        assert!((total.as_secs_f64() - expected).abs() < 1e-4);
+        assert!(ramp.is_finished());
+        assert_eq!(ramp.bpm(), 240.0);
+    }
+
+    #[test]
+    fn test_exponential_ramp_midpoint() {
+        let curve = TempoCurve::Exponential;
+        assert!((curve.interpolate(100.0, 400.0, 0.5) - 200.0).abs() < 1e-3);
+        assert_eq!(TempoCurve::Linear.interpolate(100.0, 400.0, 0.5), 250.0);
+    }
+
+    #[test]
+    fn test_ramps_stay_in_tempo_range() {
+        let change = TempoChange {
+            bpm: 0.0,
+            bars: u32::MAX,
+            curve: TempoCurve::Exponential,
+        };
+        let mut ramp = TempoRamp::new(-5.0, change, TimeSignature::COMMON);
+        assert_eq!(ramp.target(), Sequencer::MIN_BPM);
+        assert_eq!(ramp.bpm(), Sequencer::MIN_BPM);
+        assert!((ramp.advance(96).as_secs_f64() - 3.0).abs() < 1e-9);
+
+        assert_eq!(TempoChange::new(f32::NAN).bpm, Sequencer::MIN_BPM);
+        assert_eq!(TempoChange::new(5000.0).bpm, Sequencer::MAX_BPM);
+        let long = TempoChange::ramp(90.0, u32::MAX, TempoCurve::Linear);
+        assert_eq!(long.bars, TempoChange::MAX_BARS);
+    }
+
+    #[test]
+    fn test_immediate_change() {
+        let mut ramp = TempoRamp::new(90.0, TempoChange::new(150.0), TimeSignature::COMMON);
+        assert!(ramp.is_finished());
+        assert!((ramp.advance(24).as_secs_f64() - 0.1).abs() < 1e-9);
+        assert_eq!(
+            ramp.smf_tempos(0, 24),
+            vec![SmfTempo {
+                tick: 0,
+                bpm: 150.0
+            }]
+        );
+    }
+}
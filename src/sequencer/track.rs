@@ -0,0 +1,130 @@
+//! Tracks - grid rows played in parallel, each with its own loop settings
+use super::Direction;
+use std::time::Duration;
+
+/// Track speed relative to the master step clock: `steps` track steps in
+/// the time of `per` master steps, so 3/2 plays sixteenth-note triplets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rate {
+    steps: u32,
+    per: u32,
+}
+
+impl Rate {
+    pub const NORMAL: Rate = Rate::new(1, 1);
+
+    pub const PRESETS: [Rate; 11] = [
+        Rate::new(1, 4),
+        Rate::new(1, 3),
+        Rate::new(1, 2),
+        Rate::new(2, 3),
+        Rate::new(3, 4),
+        Rate::NORMAL,
+        Rate::new(4, 3),
+        Rate::new(3, 2),
+        Rate::new(2, 1),
+        Rate::new(3, 1),
+        Rate::new(4, 1),
+    ];
+
+    /// Zero terms are treated as one
+    pub const fn new(steps: u32, per: u32) -> Self {
+        Self {
+            steps: if steps == 0 { 1 } else { steps },
+            per: if per == 0 { 1 } else { per },
+        }
+    }
+
+    pub fn steps(&self) -> u32 {
+        self.steps
+    }
+
+    pub fn per(&self) -> u32 {
+        self.per
+    }
+
+    pub fn name(&self) -> String {
+        if self.per == 1 {
+            format!("{}x", self.steps)
+        } else {
+            format!("{}/{}x", self.steps, self.per)
+        }
+    }
+
+    /// Length of one track step given the master step length
+    pub fn step_duration(&self, master: Duration) -> Duration {
+        master * self.per / self.steps
+    }
+}
+
+impl Default for Rate {
+    fn default() -> Self {
+        Self::NORMAL
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Track {
+    /// Loop length in steps; tracks of different lengths phase against each other
+    length: usize,
+    direction: Direction,
+    rate: Rate,
+}
+
+impl Track {
+    pub const MAX_LENGTH: usize = 64;
+
+    pub fn new(length: usize) -> Self {
+        Self {
+            length: length.clamp(1, Self::MAX_LENGTH),
+            direction: Direction::Forward,
+            rate: Rate::NORMAL,
+        }
+    }
+
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    pub fn set_length(&mut self, length: usize) {
+        self.length = length.clamp(1, Self::MAX_LENGTH);
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    pub fn set_direction(&mut self, direction: Direction) {
+        self.direction = direction;
+    }
+
+    pub fn rate(&self) -> Rate {
+        self.rate
+    }
+
+    pub fn set_rate(&mut self, rate: Rate) {
+        self.rate = rate;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_step_duration() {
+        let master = Duration::from_millis(120);
+        assert_eq!(Rate::NORMAL.step_duration(master), master);
+        assert_eq!(
+            Rate::new(1, 4).step_duration(master),
+            Duration::from_millis(480)
+        );
+        assert_eq!(
+            Rate::new(3, 2).step_duration(master),
+            Duration::from_millis(80)
+        );
+        assert_eq!(Rate::new(0, 0), Rate::NORMAL);
+        assert_eq!(Rate::new(3, 2).name(), "3/2x");
+        assert_eq!(Rate::new(2, 1).name(), "2x");
+    }
+}
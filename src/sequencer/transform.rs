@@ -0,0 +1,211 @@
+//! Pattern transforms - shifting, reversing, inverting, mirroring and transposing
+use super::{Grid, Step};
+use crate::midi::scale::Scale;
+
+impl Grid {
+    /// Cells of row `y` that play: the track's length in track mode, else the whole row
+    fn row_span(&self, y: usize) -> usize {
+        match self.track(y) {
+            Some(track) if self.track_mode() => track.length().min(self.width()),
+            _ => self.width(),
+        }
+    }
+
+    /// Move a row's steps later (positive) or earlier (negative), wrapping around
+    pub fn shift_row(&mut self, y: usize, offset: i32) {
+        let span = self.row_span(y);
+        let mut steps: Vec<_> = (0..span).map(|x| self.step(x, y)).collect();
+        if steps.is_empty() {
+            return;
+        }
+        steps.rotate_right(offset.rem_euclid(span as i32) as usize);
+        self.write_cells((0..span).map(|x| (x, y)), steps);
+    }
+
+    /// Shift every row left or right with wraparound
+    pub fn shift(&mut self, offset: i32) {
+        for y in 0..self.height() {
+            self.shift_row(y, offset);
+        }
+    }
+
+    /// Cycle whole rows down (positive) or up (negative)
+    pub fn rotate_rows(&mut self, offset: i32) {
+        for x in 0..self.width() {
+            self.rotate_column(x, offset);
+        }
+    }
+
+    /// Cycle one column's steps down (positive) or up (negative)
+    pub fn rotate_column(&mut self, x: usize, offset: i32) {
+        let height = self.height();
+        let mut steps: Vec<_> = (0..height).map(|y| self.step(x, y)).collect();
+        if steps.is_empty() {
+            return;
+        }
+        steps.rotate_right(offset.rem_euclid(height as i32) as usize);
+        self.write_cells((0..height).map(|y| (x, y)), steps);
+    }
+
+    /// Play a row's steps backwards
+    pub fn reverse_row(&mut self, y: usize) {
+        let span = self.row_span(y);
+        let steps: Vec<_> = (0..span).rev().map(|x| self.step(x, y)).collect();
+        self.write_cells((0..span).map(|x| (x, y)), steps);
+    }
+
+    /// Play the whole pattern backwards along its traversal path
+    pub fn reverse(&mut self) {
+        let cells: Vec<_> = (0..self.step_count())
+            .filter_map(|index| self.cell_at(index))
+            .collect();
+        let steps: Vec<_> = cells.iter().rev().map(|&(x, y)| self.step(x, y)).collect();
+        self.write_cells(cells.into_iter(), steps);
+    }
+
+    /// Turn active steps off and inactive steps on in one row
+    pub fn invert_row(&mut self, y: usize) {
+        for x in 0..self.row_span(y) {
+            self.toggle(x, y);
+        }
+    }
+
+    pub fn invert(&mut self) {
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                self.toggle(x, y);
+            }
+        }
+    }
+
+    /// Flip left to right
+    pub fn mirror_horizontal(&mut self) {
+        for y in 0..self.height() {
+            let steps: Vec<_> = (0..self.width()).rev().map(|x| self.step(x, y)).collect();
+            self.write_cells((0..self.width()).map(|x| (x, y)), steps);
+        }
+    }
+
+    /// Flip top to bottom
+    pub fn mirror_vertical(&mut self) {
+        for x in 0..self.width() {
+            let steps: Vec<_> = (0..self.height()).rev().map(|y| self.step(x, y)).collect();
+            self.write_cells((0..self.height()).map(|y| (x, y)), steps);
+        }
+    }
+
+    /// Move every step's pitch by whole scale degrees
+    pub fn transpose_degrees(&mut self, degrees: i32) {
+        self.map_degrees(|degree| degree + degrees);
+    }
+
+    /// Move every step's pitch by semitones, snapping to the nearest degree
+    /// of `scale` when the result falls between its notes. Ties snap in the
+    /// direction of travel, and a non-zero shift always moves at least one
+    /// degree, so repeated small steps never get stuck.
+    pub fn transpose_semitones(&mut self, semitones: i32, scale: &Scale) {
+        let direction = semitones.signum();
+        self.map_degrees(|degree| {
+            let target = scale.semitones_for_degree(degree) + semitones;
+            let nearest = scale.degree_toward_semitones(target, direction);
+            if nearest == degree {
+                degree + direction
+            } else {
+                nearest
+            }
+        });
+    }
+
+    fn map_degrees(&mut self, f: impl Fn(i32) -> i32) {
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                if let Some(step) = self.step_mut(x, y) {
+                    step.degree = f(step.degree as i32).clamp(i8::MIN as i32, i8::MAX as i32) as i8;
+                }
+            }
+        }
+    }
+
+    fn write_cells(&mut self, cells: impl Iterator<Item = (usize, usize)>, steps: Vec<Step>) {
+        for ((x, y), step) in cells.zip(steps) {
+            if let Some(cell) = self.step_mut(x, y) {
+                *cell = step;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sequencer::fixtures::{row, set_row};
+
+    #[test]
+    fn test_shift_and_reverse_row() {
+        let mut grid = Grid::new(6, 2);
+        set_row(&mut grid, 0, "xx.x..");
+        grid.shift_row(0, 2);
+        assert_eq!(row(&grid, 0), "..xx.x");
+        grid.shift_row(0, -3);
+        assert_eq!(row(&grid, 0), "x.x..x");
+        grid.reverse_row(0);
+        assert_eq!(row(&grid, 0), "x..x.x");
+    }
+
+    #[test]
+    fn test_track_transforms_stay_within_track_length() {
+        let mut grid = Grid::new(6, 1);
+        set_row(&mut grid, 0, "x.....");
+        grid.set_track_mode(true);
+        grid.track_mut(0).unwrap().set_length(3);
+        grid.shift_row(0, -1);
+        assert_eq!(row(&grid, 0), "..x...");
+        grid.invert_row(0);
+        assert_eq!(row(&grid, 0), "xx....");
+    }
+
+    #[test]
+    fn test_mirror_and_rotate_rows() {
+        let mut grid = Grid::new(3, 3);
+        grid.clear();
+        grid.set(0, 0, true);
+        grid.mirror_horizontal();
+        assert!(grid.get(2, 0));
+        grid.mirror_vertical();
+        assert!(grid.get(2, 2));
+        grid.rotate_rows(1);
+        assert!(grid.get(2, 0));
+    }
+
+    #[test]
+    fn test_transpose() {
+        let mut grid = Grid::new(1, 1);
+        grid.transpose_degrees(3);
+        assert_eq!(grid.step(0, 0).degree, 3);
+
+        // Degree 3 of C major is F; up a semitone is F#, which snaps up to G
+        grid.transpose_semitones(1, &Scale::Major);
+        assert_eq!(grid.step(0, 0).degree, 4);
+        grid.transpose_semitones(2, &Scale::Major);
+        assert_eq!(grid.step(0, 0).degree, 5);
+        grid.transpose_semitones(-12, &Scale::Major);
+        assert_eq!(grid.step(0, 0).degree, -2);
+        grid.transpose_semitones(0, &Scale::Major);
+        assert_eq!(grid.step(0, 0).degree, -2);
+    }
+
+    #[test]
+    fn test_semitone_steps_always_move() {
+        for scale in [Scale::Major, Scale::MajorPentatonic] {
+            let mut grid = Grid::new(1, 1);
+            for expected in 1..=10 {
+                grid.transpose_semitones(1, &scale);
+                assert_eq!(grid.step(0, 0).degree, expected);
+            }
+            for expected in (0..10).rev() {
+                grid.transpose_semitones(-1, &scale);
+                assert_eq!(grid.step(0, 0).degree, expected);
+            }
+        }
+    }
+}
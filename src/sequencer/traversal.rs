@@ -0,0 +1,207 @@
+//! Grid traversal - how a flat step index maps onto (x, y) cells
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Traversal {
+    /// Left to right, top to bottom
+    #[default]
+    RowMajor,
+    /// Row by row, alternating direction (boustrophedon)
+    Snake,
+    /// Top to bottom, left to right
+    ColumnMajor,
+    /// Clockwise from the top-left corner towards the centre
+    Spiral,
+    /// Along anti-diagonals starting at the top-left corner
+    Diagonal,
+    /// A user-drawn sequence of cells
+    Custom(Vec<(usize, usize)>),
+}
+
+impl Traversal {
+    /// Built-in traversals, in the order the GUI lists them
+    pub const PRESETS: [Traversal; 5] = [
+        Traversal::RowMajor,
+        Traversal::Snake,
+        Traversal::ColumnMajor,
+        Traversal::Spiral,
+        Traversal::Diagonal,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Traversal::RowMajor => "Rows",
+            Traversal::Snake => "Snake",
+            Traversal::ColumnMajor => "Columns",
+            Traversal::Spiral => "Spiral",
+            Traversal::Diagonal => "Diagonal",
+            Traversal::Custom(_) => "Custom",
+        }
+    }
+
+    /// Number of steps in one pass over a `width` x `height` grid
+    pub fn len(&self, width: usize, height: usize) -> usize {
+        match self {
+            Traversal::Custom(path) => path
+                .iter()
+                .filter(|&&(x, y)| x < width && y < height)
+                .count(),
+            _ => width * height,
+        }
+    }
+
+    pub fn is_empty(&self, width: usize, height: usize) -> bool {
+        self.len(width, height) == 0
+    }
+
+    /// Cell visited at a step index, or `None` past the end of the path
+    pub fn cell_at(&self, index: usize, width: usize, height: usize) -> Option<(usize, usize)> {
+        if width == 0 || height == 0 {
+            return None;
+        }
+
+        match self {
+            Traversal::RowMajor => (index < width * height).then(|| (index % width, index / width)),
+            Traversal::Snake => (index < width * height).then(|| {
+                let y = index / width;
+                let x = index % width;
+                if y % 2 == 1 {
+                    (width - 1 - x, y)
+                } else {
+                    (x, y)
+                }
+            }),
+            Traversal::ColumnMajor => {
+                (index < width * height).then(|| (index / height, index % height))
+            }
+            _ => self.path(width, height).get(index).copied(),
+        }
+    }
+
+    /// Every cell in visiting order
+    pub fn path(&self, width: usize, height: usize) -> Vec<(usize, usize)> {
+        match self {
+            Traversal::Spiral => spiral(width, height),
+            Traversal::Diagonal => diagonal(width, height),
+            Traversal::Custom(path) => path
+                .iter()
+                .copied()
+                .filter(|&(x, y)| x < width && y < height)
+                .collect(),
+            _ => (0..width * height)
+                .filter_map(|i| self.cell_at(i, width, height))
+                .collect(),
+        }
+    }
+
+    /// Inverse mapping: the first step index that visits a cell
+    pub fn index_of(&self, x: usize, y: usize, width: usize, height: usize) -> Option<usize> {
+        self.path(width, height)
+            .iter()
+            .position(|&cell| cell == (x, y))
+    }
+}
+
+fn spiral(width: usize, height: usize) -> Vec<(usize, usize)> {
+    let mut cells = Vec::with_capacity(width * height);
+    let (mut left, mut top) = (0, 0);
+    let (mut right, mut bottom) = (width, height);
+
+    while left < right && top < bottom {
+        cells.extend((left..right).map(|x| (x, top)));
+        cells.extend((top + 1..bottom).map(|y| (right - 1, y)));
+        if bottom - top > 1 {
+            cells.extend((left..right - 1).rev().map(|x| (x, bottom - 1)));
+        }
+        if right - left > 1 {
+            cells.extend((top + 1..bottom - 1).rev().map(|y| (left, y)));
+        }
+        left += 1;
+        top += 1;
+        right -= 1;
+        bottom -= 1;
+    }
+
+    cells
+}
+
+fn diagonal(width: usize, height: usize) -> Vec<(usize, usize)> {
+    if width == 0 || height == 0 {
+        return Vec::new();
+    }
+    let mut cells = Vec::with_capacity(width * height);
+    for d in 0..width + height - 1 {
+        for y in 0..height.min(d + 1) {
+            let x = d - y;
+            if x < width {
+                cells.push((x, y));
+            }
+        }
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snake_and_columns() {
+        assert_eq!(
+            Traversal::Snake.path(3, 2),
+            vec![(0, 0), (1, 0), (2, 0), (2, 1), (1, 1), (0, 1)]
+        );
+        assert_eq!(
+            Traversal::ColumnMajor.path(2, 2),
+            vec![(0, 0), (0, 1), (1, 0), (1, 1)]
+        );
+    }
+
+    #[test]
+    fn test_spiral_visits_every_cell_once() {
+        assert_eq!(
+            Traversal::Spiral.path(3, 3),
+            vec![
+                (0, 0),
+                (1, 0),
+                (2, 0),
+                (2, 1),
+                (2, 2),
+                (1, 2),
+                (0, 2),
+                (0, 1),
+                (1, 1)
+            ]
+        );
+        for (w, h) in [(4, 2), (1, 5), (5, 1), (8, 8)] {
+            let mut path = Traversal::Spiral.path(w, h);
+            path.sort_unstable();
+            path.dedup();
+            assert_eq!(path.len(), w * h);
+        }
+    }
+
+    #[test]
+    fn test_diagonal() {
+        assert_eq!(
+            Traversal::Diagonal.path(2, 3),
+            vec![(0, 0), (1, 0), (0, 1), (1, 1), (0, 2), (1, 2)]
+        );
+    }
+
+    #[test]
+    fn test_empty_grid_has_empty_path() {
+        for traversal in Traversal::PRESETS {
+            assert!(traversal.path(0, 0).is_empty());
+            assert!(traversal.path(0, 3).is_empty());
+            assert_eq!(traversal.cell_at(0, 3, 0), None);
+        }
+    }
+
+    #[test]
+    fn test_custom_path_skips_out_of_range_cells() {
+        let traversal = Traversal::Custom(vec![(1, 1), (9, 9), (0, 0)]);
+        assert_eq!(traversal.len(2, 2), 2);
+        assert_eq!(traversal.cell_at(1, 2, 2), Some((0, 0)));
+        assert_eq!(traversal.index_of(1, 1, 2, 2), Some(0));
+    }
+}